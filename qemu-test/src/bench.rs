@@ -0,0 +1,97 @@
+//! benchmarks of the optional optimizations of the crate, run after the checks when the
+//! `bench` feature is enabled; times are in ticks of the `time` csr
+
+use alloc::vec::Vec;
+use memory_set::{AccessType, ElfLoadPolicy, MapPermission, MemorySet, DEFAULT_EAGER_COPY_PAGES};
+use page_table::{VirtAddr, PAGE_SIZE};
+
+use crate::{strampoline, user_elf, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
+
+const ROUNDS: usize = 64;
+
+/// data pages of the elf loaded by the exec benchmark
+const EXEC_DATA_PAGES: usize = 256;
+
+fn ticks() -> usize {
+    let ticks;
+    unsafe { core::arch::asm!("rdtime {}", out(reg) ticks) };
+    ticks
+}
+
+/// average ticks of `f` over `ROUNDS` runs
+fn measure(mut f: impl FnMut()) -> usize {
+    let start = ticks();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    (ticks() - start) / ROUNDS
+}
+
+fn load(elf: &[u8]) -> MemorySet {
+    MemorySet::from_elf(
+        elf,
+        TRAMPOLINE,
+        strampoline as usize,
+        TRAP_CONTEXT,
+        USER_STACK_SIZE,
+    )
+    .expect("the embedded elf is valid")
+    .0
+}
+
+pub fn run() {
+    fork_small_areas();
+    exec_physical_order();
+}
+
+/// Fork copy-on-write and write every writable page in the child right away, like a process
+/// touching its data, stack and trap context after fork, with small areas copied eagerly or
+/// not, see `MemorySet::set_eager_copy_threshold`.
+fn fork_small_areas() {
+    let elf = user_elf::build();
+    for threshold in [0, DEFAULT_EAGER_COPY_PAGES] {
+        let mut parent = load(&elf);
+        parent.set_eager_copy_threshold(threshold);
+        let writable = parent
+            .areas()
+            .filter(|area| area.permission().contains(MapPermission::W))
+            .flat_map(|area| (area.start_va().0..area.end_va().0).step_by(PAGE_SIZE))
+            .collect::<Vec<_>>();
+        let ticks = measure(|| {
+            let mut child = MemorySet::clone_cow(&mut parent).expect("out of frames forking");
+            for va in writable.iter() {
+                child.handle_page_fault(VirtAddr::from(*va), AccessType::Write);
+            }
+        });
+        println!(
+            "bench: fork and write {} pages, eager copy up to {} pages: {} ticks",
+            writable.len(),
+            threshold,
+            ticks
+        );
+    }
+}
+
+/// Load an elf with large segments, with its frames filled in allocation order or in
+/// ascending physical order, see `ElfLoadPolicy::with_physical_order_copy`.
+fn exec_physical_order() {
+    let elf = user_elf::build_with_data_pages(EXEC_DATA_PAGES);
+    for physical_order_copy in [false, true] {
+        let policy = ElfLoadPolicy::new().with_physical_order_copy(physical_order_copy);
+        let ticks = measure(|| {
+            MemorySet::from_elf_with_policy(
+                &elf,
+                TRAMPOLINE,
+                strampoline as usize,
+                TRAP_CONTEXT,
+                USER_STACK_SIZE,
+                &policy,
+            )
+            .expect("the embedded elf is valid");
+        });
+        println!(
+            "bench: exec {} data pages, physical order copy {}: {} ticks",
+            EXEC_DATA_PAGES, physical_order_copy, ticks
+        );
+    }
+}
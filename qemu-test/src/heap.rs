@@ -0,0 +1,47 @@
+//! a bump allocator, the test kernel runs once and never needs memory back
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// large enough for all rounds of the benchmarks, nothing is ever freed
+const HEAP_SIZE: usize = 32 << 20;
+
+struct Heap {
+    memory: UnsafeCell<[u8; HEAP_SIZE]>,
+    next: AtomicUsize,
+}
+
+unsafe impl Sync for Heap {}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let base = self.memory.get() as usize;
+        let mut next = self.next.load(Ordering::Relaxed);
+        loop {
+            let start = (base + next).next_multiple_of(layout.align()) - base;
+            let end = start + layout.size();
+            if end > HEAP_SIZE {
+                return ptr::null_mut();
+            }
+            match self
+                .next
+                .compare_exchange(next, end, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return (base + start) as *mut u8,
+                Err(current) => next = current,
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+}
+
+#[global_allocator]
+static HEAP: Heap = Heap {
+    memory: UnsafeCell::new([0; HEAP_SIZE]),
+    next: AtomicUsize::new(0),
+};
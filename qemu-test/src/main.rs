@@ -0,0 +1,226 @@
+//! Integration test kernel for qemu-system-riscv64. It builds the kernel space and user
+//! spaces with the crate on the real page table, loads an embedded elf, forks it
+//! copy-on-write and reports each check over the sbi console. `cargo run --release` in this
+//! directory boots it; qemu exits with status 0 if all checks passed.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+mod sbi;
+
+#[cfg(feature = "bench")]
+mod bench;
+mod heap;
+mod user_elf;
+
+use core::panic::PanicInfo;
+
+use memory_set::{AccessType, FaultResult, MapPermission, MemorySet, MemorySetBuilder};
+use page_table::{PTEFlags, PageTableEntry, PhysAddr, VirtAddr, PAGE_SIZE};
+
+core::arch::global_asm!(include_str!("entry.asm"));
+
+/// end of the physical memory of the qemu virt machine with its default 128 MiB
+const MEMORY_END: usize = 0x8800_0000;
+const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
+const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+const USER_STACK_SIZE: usize = 2 * PAGE_SIZE;
+
+extern "C" {
+    fn stext();
+    fn etext();
+    fn srodata();
+    fn erodata();
+    fn sdata();
+    fn edata();
+    fn sbss();
+    fn ebss();
+    fn ekernel();
+    fn strampoline();
+}
+
+#[derive(Default)]
+struct Report {
+    passed: usize,
+    failed: usize,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, ok: bool) {
+        if ok {
+            self.passed += 1;
+            println!("[ ok ] {}", name);
+        } else {
+            self.failed += 1;
+            println!("[FAIL] {}", name);
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn rust_main() -> ! {
+    clear_bss();
+    page_table::init_frame_allocator(
+        PhysAddr::from(ekernel as usize).ceil(),
+        PhysAddr::from(MEMORY_END).floor(),
+    );
+    let mut report = Report::default();
+    let kernel_space = kernel_space(&mut report);
+    exec_and_fork(&mut report);
+    #[cfg(feature = "bench")]
+    bench::run();
+    drop(kernel_space);
+    println!(
+        "qemu-test: {} passed, {} failed",
+        report.passed, report.failed
+    );
+    sbi::shutdown(report.failed > 0)
+}
+
+fn clear_bss() {
+    unsafe {
+        core::slice::from_raw_parts_mut(sbss as usize as *mut u8, ebss as usize - sbss as usize)
+            .fill(0);
+    }
+}
+
+fn pte(memory_set: &MemorySet, va: usize) -> Option<PageTableEntry> {
+    memory_set
+        .translate(VirtAddr::from(va).floor())
+        .filter(|pte| pte.is_valid())
+}
+
+fn page(pte: PageTableEntry) -> &'static mut [u8] {
+    pte.ppn().get_bytes_array()
+}
+
+/// map the kernel identically, with the trampoline on top, and switch to it
+fn kernel_space(report: &mut Report) -> MemorySet {
+    let r = MapPermission::R;
+    let rw = MapPermission::R | MapPermission::W;
+    let rx = MapPermission::R | MapPermission::X;
+    let kernel_space = MemorySetBuilder::new()
+        .map_trampoline(TRAMPOLINE, strampoline as usize)
+        .push_identical(stext as usize, etext as usize, rx)
+        .push_identical(srodata as usize, erodata as usize, r)
+        .push_identical(sdata as usize, edata as usize, rw)
+        .push_identical(edata as usize, ebss as usize, rw)
+        .push_identical(ekernel as usize, MEMORY_END, rw)
+        .push_identical(sbi::TEST_DEVICE, sbi::TEST_DEVICE + PAGE_SIZE, rw)
+        .build();
+    report.check(
+        "kernel: text mapped identically and executable",
+        pte(&kernel_space, stext as usize).is_some_and(|pte| {
+            PhysAddr::from(pte.ppn()).0 == stext as usize && pte.executable() && !pte.writable()
+        }),
+    );
+    report.check(
+        "kernel: trampoline mapped on top",
+        pte(&kernel_space, TRAMPOLINE)
+            .is_some_and(|pte| PhysAddr::from(pte.ppn()).0 == strampoline as usize),
+    );
+    kernel_space.activate(0);
+    report.check("kernel: running on the kernel space", true);
+    kernel_space
+}
+
+/// load the embedded elf, fork it copy-on-write and break the sharing with a write
+fn exec_and_fork(report: &mut Report) {
+    let elf = user_elf::build();
+    let loaded = MemorySet::from_elf(
+        &elf,
+        TRAMPOLINE,
+        strampoline as usize,
+        TRAP_CONTEXT,
+        USER_STACK_SIZE,
+    );
+    report.check("exec: elf accepted", loaded.is_ok());
+    let Ok((mut parent, user_sp, entry)) = loaded else {
+        return;
+    };
+    report.check("exec: entry point", entry == user_elf::ENTRY);
+    report.check(
+        "exec: text mapped user executable",
+        pte(&parent, user_elf::ENTRY).is_some_and(|pte| {
+            pte.flags().contains(PTEFlags::U | PTEFlags::X)
+                && page(pte)[..user_elf::CODE.len()] == user_elf::CODE
+        }),
+    );
+    report.check(
+        "exec: data copied",
+        pte(&parent, user_elf::DATA_VA).is_some_and(|pte| {
+            page(pte)
+                .iter()
+                .enumerate()
+                .all(|(offset, byte)| *byte == user_elf::data_byte(offset))
+        }),
+    );
+    report.check(
+        "exec: bss zeroed",
+        pte(&parent, user_elf::BSS_VA).is_some_and(|pte| page(pte).iter().all(|byte| *byte == 0)),
+    );
+    let mut stack = [0; 5];
+    report.check(
+        "exec: user stack round trip",
+        parent
+            .copy_to_user(VirtAddr::from(user_sp - 16), b"stack")
+            .and_then(|_| parent.copy_from_user(&mut stack, VirtAddr::from(user_sp - 16)))
+            .is_ok()
+            && &stack == b"stack",
+    );
+
+    // copy even the smallest areas lazily
+    parent.set_eager_copy_threshold(0);
+    let Some(mut child) = MemorySet::clone_cow(&mut parent) else {
+        report.check("fork: frames to swap in", false);
+        return;
+    };
+    let (Some(parent_pte), Some(child_pte)) = (
+        pte(&parent, user_elf::DATA_VA),
+        pte(&child, user_elf::DATA_VA),
+    ) else {
+        report.check("fork: data mapped in both", false);
+        return;
+    };
+    report.check(
+        "fork: data frame shared read-only",
+        parent_pte.ppn() == child_pte.ppn() && !parent_pte.writable() && !child_pte.writable(),
+    );
+    report.check(
+        "fork: trampoline mapped in the child",
+        pte(&child, TRAMPOLINE)
+            .is_some_and(|pte| PhysAddr::from(pte.ppn()).0 == strampoline as usize),
+    );
+    report.check(
+        "fork: write fault in the child resolved",
+        child.handle_page_fault(VirtAddr::from(user_elf::DATA_VA), AccessType::Write)
+            == FaultResult::Resolved,
+    );
+    let child_pte = pte(&child, user_elf::DATA_VA).unwrap();
+    report.check(
+        "fork: child got a writable copy",
+        child_pte.ppn() != parent_pte.ppn()
+            && child_pte.writable()
+            && page(child_pte)[1] == user_elf::data_byte(1),
+    );
+    page(child_pte)[1] = !user_elf::data_byte(1);
+    report.check(
+        "fork: parent unaffected by the child",
+        page(pte(&parent, user_elf::DATA_VA).unwrap())[1] == user_elf::data_byte(1),
+    );
+    report.check(
+        "fork: write fault in the parent resolved",
+        parent.handle_page_fault(VirtAddr::from(user_elf::DATA_VA), AccessType::Write)
+            == FaultResult::Resolved
+            && pte(&parent, user_elf::DATA_VA).is_some_and(|pte| pte.writable()),
+    );
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("qemu-test: panicked: {}", info);
+    sbi::shutdown(true)
+}
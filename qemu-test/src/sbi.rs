@@ -0,0 +1,48 @@
+//! console output and shutdown through the sbi firmware and the qemu test device
+
+use core::fmt::{self, Write};
+
+/// the sifive test device of the qemu virt machine, mapped identically by the kernel space
+pub const TEST_DEVICE: usize = 0x10_0000;
+
+const CONSOLE_PUTCHAR: usize = 1;
+
+fn sbi_call(eid: usize, arg0: usize) -> usize {
+    let ret;
+    unsafe {
+        core::arch::asm!("ecall", inlateout("a0") arg0 => ret, in("a7") eid);
+    }
+    ret
+}
+
+struct Console;
+
+impl Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            sbi_call(CONSOLE_PUTCHAR, byte as usize);
+        }
+        Ok(())
+    }
+}
+
+pub fn print(args: fmt::Arguments) {
+    Console.write_fmt(args).unwrap();
+}
+
+macro_rules! println {
+    ($($arg:tt)*) => {
+        $crate::sbi::print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// power off qemu, its exit status is 0 on success and 1 on failure
+pub fn shutdown(failure: bool) -> ! {
+    let code: u32 = if failure { 1 << 16 | 0x3333 } else { 0x5555 };
+    unsafe {
+        (TEST_DEVICE as *mut u32).write_volatile(code);
+    }
+    loop {
+        unsafe { core::arch::asm!("wfi") };
+    }
+}
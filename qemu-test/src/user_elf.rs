@@ -0,0 +1,92 @@
+//! a minimal riscv64 executable, assembled in memory: a text page exiting through the
+//! `exit` syscall and data pages followed by two pages of bss
+
+use alloc::vec::Vec;
+use page_table::PAGE_SIZE;
+
+pub const ENTRY: usize = 0x1_0000;
+pub const DATA_VA: usize = 0x1_1000;
+pub const BSS_VA: usize = DATA_VA + PAGE_SIZE;
+const BSS_PAGES: usize = 2;
+
+/// `li a7, 93; li a0, 0; ecall`
+pub const CODE: [u8; 12] = [
+    0x93, 0x08, 0xd0, 0x05, 0x13, 0x05, 0x00, 0x00, 0x73, 0x00, 0x00, 0x00,
+];
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const TEXT_OFFSET: usize = PAGE_SIZE;
+const DATA_OFFSET: usize = 2 * PAGE_SIZE;
+
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+/// content of the data page
+pub fn data_byte(offset: usize) -> u8 {
+    (offset % 251) as u8
+}
+
+fn push_phdr(
+    elf: &mut Vec<u8>,
+    flags: u32,
+    offset: usize,
+    va: usize,
+    file_size: usize,
+    mem_size: usize,
+) {
+    elf.extend_from_slice(&PT_LOAD.to_le_bytes());
+    elf.extend_from_slice(&flags.to_le_bytes());
+    for field in [offset, va, va, file_size, mem_size, PAGE_SIZE] {
+        elf.extend_from_slice(&(field as u64).to_le_bytes());
+    }
+}
+
+/// the executable with a single data page
+pub fn build() -> Vec<u8> {
+    build_with_data_pages(1)
+}
+
+pub fn build_with_data_pages(data_pages: usize) -> Vec<u8> {
+    let data_size = data_pages * PAGE_SIZE;
+    let mut elf = Vec::with_capacity(DATA_OFFSET + data_size);
+    // e_ident: 64-bit, little endian, version 1
+    elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    elf.extend_from_slice(&[0; 8]);
+    // e_type executable, e_machine riscv, e_version
+    elf.extend_from_slice(&2u16.to_le_bytes());
+    elf.extend_from_slice(&0xf3u16.to_le_bytes());
+    elf.extend_from_slice(&1u32.to_le_bytes());
+    // e_entry, e_phoff, e_shoff
+    elf.extend_from_slice(&(ENTRY as u64).to_le_bytes());
+    elf.extend_from_slice(&(EHDR_SIZE as u64).to_le_bytes());
+    elf.extend_from_slice(&0u64.to_le_bytes());
+    // e_flags, e_ehsize, e_phentsize, e_phnum, e_shentsize, e_shnum, e_shstrndx
+    elf.extend_from_slice(&0u32.to_le_bytes());
+    for field in [EHDR_SIZE, PHDR_SIZE, 2, 64, 0, 0] {
+        elf.extend_from_slice(&(field as u16).to_le_bytes());
+    }
+    push_phdr(
+        &mut elf,
+        PF_R | PF_X,
+        TEXT_OFFSET,
+        ENTRY,
+        CODE.len(),
+        CODE.len(),
+    );
+    push_phdr(
+        &mut elf,
+        PF_R | PF_W,
+        DATA_OFFSET,
+        DATA_VA,
+        data_size,
+        data_size + BSS_PAGES * PAGE_SIZE,
+    );
+    elf.resize(TEXT_OFFSET, 0);
+    elf.extend_from_slice(&CODE);
+    elf.resize(DATA_OFFSET, 0);
+    elf.extend((0..data_size).map(data_byte));
+    elf
+}
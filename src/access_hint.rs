@@ -0,0 +1,28 @@
+/// expected access pattern of an area, used to populate lazy areas ahead of faults
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccessHint {
+    /// no particular order, only the faulting page is populated
+    #[default]
+    Random,
+    /// ascending order, the pages following the faulting page are populated as well
+    Sequential,
+}
+
+impl AccessHint {
+    /// stable code for on-disk formats, see `WIRE_VERSION`
+    pub fn to_wire(self) -> u8 {
+        match self {
+            AccessHint::Random => 0,
+            AccessHint::Sequential => 1,
+        }
+    }
+
+    pub fn from_wire(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(AccessHint::Random),
+            1 => Some(AccessHint::Sequential),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,7 @@
+/// kind of memory access that raised a page fault
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AccessType {
+    Read,
+    Write,
+    Execute,
+}
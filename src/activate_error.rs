@@ -0,0 +1,12 @@
+/// reason `MemorySet::activate_checked` refused to install the token
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ActivateError {
+    /// no memory set is registered under the root page table, see `registry::register`
+    NotRegistered,
+    /// the root page table is registered by another memory set, the frame was reused
+    StaleRoot,
+    /// the trampoline or a fixed mapping is missing or maps something else
+    TrampolineBroken,
+    /// the canary page reads back wrong
+    CanaryBroken,
+}
@@ -0,0 +1,67 @@
+use alloc::string::String;
+use page_table::{PTEFlags, PhysPageNum, VirtAddr, VirtPageNum};
+
+use crate::{AreaClass, AreaHandle, AreaOrigin, MapPermission, MapType, PageState};
+
+/// what backs a page, see `AddressExplanation`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Backing {
+    /// nothing yet, e.g. a lazy page never accessed
+    None,
+    /// a frame tracked by the memory set, `shared` if another area maps it as well
+    Frame { ppn: PhysPageNum, shared: bool },
+    /// the physical page of the same number, of an identical area
+    Identical(PhysPageNum),
+    /// device memory of an mmio area
+    Mmio(PhysPageNum),
+    /// the swap backend, the page is not resident
+    Swapped,
+}
+
+/// the area covering an address, see `AddressExplanation`
+#[derive(Clone, Debug)]
+pub struct AreaSummary {
+    pub handle: AreaHandle,
+    pub start: VirtPageNum,
+    pub end: VirtPageNum,
+    pub map_type: MapType,
+    pub perm: MapPermission,
+    pub class: AreaClass,
+    pub origin: Option<AreaOrigin>,
+    pub tag: Option<String>,
+}
+
+/// everything the memory set knows about an address, see `MemorySet::explain`
+#[derive(Clone, Debug)]
+pub struct AddressExplanation {
+    pub va: VirtAddr,
+    pub vpn: VirtPageNum,
+    /// `None` if no area covers the address
+    pub area: Option<AreaSummary>,
+    pub state: PageState,
+    /// flags of the pte, `None` if the page is not mapped
+    pub pte_flags: Option<PTEFlags>,
+    /// software bits of the pte, see `MapArea::with_software_bits`
+    pub software_bits: u8,
+    pub backing: Backing,
+    /// the address is the trampoline
+    pub trampoline: bool,
+    /// the page was unmapped since it was mapped, see `Translation::Unmapped`
+    pub poisoned: bool,
+}
+
+impl AddressExplanation {
+    pub fn is_pinned(&self) -> bool {
+        self.state == PageState::Pinned
+    }
+
+    pub fn is_cow(&self) -> bool {
+        self.state == PageState::ResidentCow
+    }
+
+    /// whether a user access of the page would succeed without a fault
+    pub fn is_user_accessible(&self) -> bool {
+        self.pte_flags
+            .is_some_and(|flags| flags.contains(PTEFlags::V | PTEFlags::U))
+    }
+}
@@ -0,0 +1,11 @@
+use crate::FaultScratch;
+
+/// whether a frame-allocating call may block on the frame allocator,
+/// see `MemorySet::try_push` and `MemorySet::try_handle_page_fault`
+pub enum AllocPolicy<'a> {
+    /// allocate from the global frame allocator, which may block
+    Block,
+    /// only take frames already held by the scratch, the call returns `WouldBlock`
+    /// instead of allocating, so the kernel can retry from schedulable context
+    NoBlock(&'a mut FaultScratch),
+}
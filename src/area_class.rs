@@ -0,0 +1,10 @@
+/// whether an area belongs to the user program or to the kernel, see `MapArea::with_class`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AreaClass {
+    /// owned by the program, cloned on fork and reachable by copies from and to user space;
+    /// may lack U, e.g. the trap context
+    User,
+    /// owned by the kernel, never cloned nor touched on behalf of the program, must lack U
+    Kernel,
+}
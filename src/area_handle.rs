@@ -0,0 +1,13 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_HANDLE: AtomicUsize = AtomicUsize::new(0);
+
+/// identifies a map area, unique across all memory sets
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct AreaHandle(usize);
+
+impl AreaHandle {
+    pub(crate) fn new() -> Self {
+        Self(NEXT_HANDLE.fetch_add(1, Ordering::Relaxed))
+    }
+}
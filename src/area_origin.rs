@@ -0,0 +1,16 @@
+/// what created an area, to attribute memory use to loader behavior or syscalls
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AreaOrigin {
+    /// mapped by the loader of the program
+    Exec,
+    Brk,
+    Mmap,
+    StackGrowth,
+    /// mapped by the kernel for its own purposes
+    KernelInternal,
+}
+
+impl AreaOrigin {
+    pub const COUNT: usize = 5;
+}
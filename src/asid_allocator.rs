@@ -0,0 +1,54 @@
+use alloc::vec::Vec;
+
+use crate::hw;
+
+/// bits of the asid field of satp in sv39
+pub const MAX_ASID_BITS: usize = 16;
+
+/// Hands out address space ids for `MemorySet::set_asid` and takes them back for reuse.
+/// ASID 0 is never handed out, memory sets without an id use it and flush the whole tlb
+/// on activation. The entries of the others are flushed on the calling hart when an id is
+/// taken back and when it is handed out again, other harts flush them when they first
+/// activate a memory set using the id, see `MemorySet::activate`.
+pub struct AsidAllocator {
+    next: usize,
+    limit: usize,
+    free: Vec<usize>,
+}
+
+impl AsidAllocator {
+    /// ids up to the `bits` the hart implements, see the `ASIDLEN` of satp
+    pub fn new(bits: usize) -> Self {
+        Self {
+            next: 1,
+            limit: 1 << bits.min(MAX_ASID_BITS),
+            free: Vec::new(),
+        }
+    }
+
+    /// `None` once all ids are taken
+    pub fn alloc(&mut self) -> Option<usize> {
+        if let Some(asid) = self.free.pop() {
+            hw::flush_asid(asid);
+            return Some(asid);
+        }
+        if self.next < self.limit {
+            self.next += 1;
+            Some(self.next - 1)
+        } else {
+            None
+        }
+    }
+
+    /// take `asid` back and flush the tlb entries tagged with it
+    pub fn dealloc(&mut self, asid: usize) {
+        debug_assert!(asid != 0 && asid < self.next && !self.free.contains(&asid));
+        hw::flush_asid(asid);
+        self.free.push(asid);
+    }
+
+    /// ids handed out and not taken back
+    pub fn in_use(&self) -> usize {
+        self.next - 1 - self.free.len()
+    }
+}
@@ -0,0 +1,10 @@
+use crate::{LoadResult, MemorySet, ProgramLayout};
+
+/// a format of executables, see `MemorySet::load_program`
+pub trait BinFmt {
+    /// whether `data` is in this format
+    fn probe(&self, data: &[u8]) -> bool;
+
+    /// map the program in `data` into `memory_set`
+    fn load(&self, memory_set: &mut MemorySet, data: &[u8], layout: &ProgramLayout) -> LoadResult;
+}
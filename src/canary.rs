@@ -0,0 +1,49 @@
+use page_table::{PageTable, VirtAddr, VirtPageNum, PAGE_SIZE};
+
+use crate::pte_ext;
+
+/// A page of a memory set filled with a value only the memory set knows, see
+/// `MemorySet::enable_canary`. It reads back wrong once the page table frames were freed
+/// and reused, e.g. when a token outlives its memory set.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Canary {
+    vpn: VirtPageNum,
+    value: u64,
+}
+
+impl Canary {
+    pub(crate) fn new(vpn: VirtPageNum, value: u64) -> Self {
+        Self { vpn, value }
+    }
+
+    pub fn va(&self) -> VirtAddr {
+        self.vpn.into()
+    }
+
+    pub(crate) fn fill(&self, page: &mut [u8]) {
+        for word in page.chunks_exact_mut(8) {
+            word.copy_from_slice(&self.value.to_ne_bytes());
+        }
+    }
+
+    /// whether the canary page reads back intact through the page table of `token`,
+    /// e.g. before installing a token kept in a task
+    pub fn check(&self, token: usize) -> bool {
+        let page_table = PageTable::from_token(token);
+        let Some(pte) = pte_ext::translate(&page_table, self.vpn).filter(|pte| pte.is_valid())
+        else {
+            return false;
+        };
+        pte.ppn().get_bytes_array()[..PAGE_SIZE]
+            .chunks_exact(8)
+            .all(|word| word == self.value.to_ne_bytes())
+    }
+}
+
+/// splitmix64, spreads a seed over all bits
+pub(crate) fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
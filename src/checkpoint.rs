@@ -0,0 +1,51 @@
+use crate::TransactionError;
+
+/// first bytes of a layout written by `MemorySet::serialize_layout`
+pub(crate) const MAGIC: [u8; 4] = *b"MSCK";
+
+/// reason `MemorySet::restore_layout` could not rebuild a memory set
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CheckpointError {
+    /// the data does not start with a serialized layout
+    BadMagic,
+    /// the layout was written with other wire codes, see `WIRE_VERSION`
+    UnsupportedVersion(u16),
+    /// the data ends in the middle of the layout
+    Truncated,
+    /// a map type, permission or clone strategy code is unknown
+    BadCode,
+    /// an area could not be mapped again
+    Map(TransactionError),
+}
+
+/// cursor over a serialized layout
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], CheckpointError> {
+        if self.data.len() < len {
+            return Err(CheckpointError::Truncated);
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, CheckpointError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, CheckpointError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, CheckpointError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
@@ -0,0 +1,42 @@
+use alloc::collections::{btree_set::BTreeSet, vec_deque::VecDeque};
+use page_table::VirtPageNum;
+
+/// resident pages in the order the reclaim clock visits them, see `MemorySet::reclaim`
+pub(crate) struct Clock {
+    queue: VecDeque<VirtPageNum>,
+    queued: BTreeSet<VirtPageNum>,
+}
+
+impl Clock {
+    pub(crate) fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            queued: BTreeSet::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// queue the pages not queued yet behind the others
+    pub(crate) fn extend(&mut self, pages: impl IntoIterator<Item = VirtPageNum>) {
+        for vpn in pages {
+            if self.queued.insert(vpn) {
+                self.queue.push_back(vpn);
+            }
+        }
+    }
+
+    /// the page under the hand, it is forgotten unless requeued
+    pub(crate) fn advance(&mut self) -> Option<VirtPageNum> {
+        let vpn = self.queue.pop_front()?;
+        self.queued.remove(&vpn);
+        Some(vpn)
+    }
+
+    /// give the page another round
+    pub(crate) fn requeue(&mut self, vpn: VirtPageNum) {
+        self.extend([vpn]);
+    }
+}
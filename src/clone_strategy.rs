@@ -0,0 +1,43 @@
+/// copy-on-write areas of at most this many pages are copied eagerly on clone, by default
+pub const DEFAULT_EAGER_COPY_PAGES: usize = 2;
+
+/// how an area is duplicated when its memory set is cloned, see `MemorySet::from_existed_user`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CloneStrategy {
+    /// allocate new frames and copy the data
+    #[default]
+    EagerCopy,
+    /// share the frames read-only, the first write to a page copies it
+    CoW,
+    /// share the frames, writes are visible in both memory sets
+    Share,
+    /// allocate new zeroed frames
+    Zero,
+    /// leave the area out of the clone
+    Skip,
+}
+
+impl CloneStrategy {
+    /// stable code for on-disk formats, see `WIRE_VERSION`
+    pub fn to_wire(self) -> u8 {
+        match self {
+            CloneStrategy::EagerCopy => 0,
+            CloneStrategy::CoW => 1,
+            CloneStrategy::Share => 2,
+            CloneStrategy::Zero => 3,
+            CloneStrategy::Skip => 4,
+        }
+    }
+
+    pub fn from_wire(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(CloneStrategy::EagerCopy),
+            1 => Some(CloneStrategy::CoW),
+            2 => Some(CloneStrategy::Share),
+            3 => Some(CloneStrategy::Zero),
+            4 => Some(CloneStrategy::Skip),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use page_table::{VPNRange, VirtPageNum};
+
+/// A range of a memory set handed to a child manager, which maps into it by
+/// `MemorySet::map_delegated` until the parent takes it back by `MemorySet::revoke_delegation`.
+pub struct DelegatedRegion {
+    id: usize,
+    range: VPNRange,
+}
+
+impl DelegatedRegion {
+    pub(crate) fn new(id: usize, range: VPNRange) -> Self {
+        Self { id, range }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn range(&self) -> VPNRange {
+        self.range
+    }
+
+    /// whether `start..end` lies within the region
+    pub fn covers(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        self.range.get_start() <= start && end <= self.range.get_end()
+    }
+}
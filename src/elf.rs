@@ -0,0 +1,60 @@
+//! elf helpers beyond loading
+
+use xmas_elf::program::ProgramHeader;
+
+use crate::ElfLoadError;
+
+/// first bytes of every elf
+pub(crate) const ELF_MAGIC: [u8; 4] = [0x7f, 0x45, 0x4c, 0x46];
+
+/// addresses and file offsets of a segment
+pub(crate) struct SegmentBounds {
+    pub start_va: usize,
+    pub end_va: usize,
+    /// end of the part of the segment backed by the file
+    pub file_end_va: usize,
+    pub offset: usize,
+    pub file_end: usize,
+}
+
+impl SegmentBounds {
+    /// Compute the bounds of `ph` on u64, as the fields of 64-bit elfs are, and convert them
+    /// checked, so that a host with a narrower usize cannot truncate them. `file_len` is the
+    /// length of the whole elf.
+    pub(crate) fn of(ph: &ProgramHeader, file_len: usize) -> Result<Self, ElfLoadError> {
+        let vaddr = ph.virtual_addr();
+        let end_va = vaddr
+            .checked_add(ph.mem_size())
+            .ok_or(ElfLoadError::AddressOverflow)?;
+        let file_end_va = vaddr
+            .checked_add(ph.file_size())
+            .ok_or(ElfLoadError::AddressOverflow)?;
+        let file_end = ph
+            .offset()
+            .checked_add(ph.file_size())
+            .ok_or(ElfLoadError::SegmentOutOfBounds)?;
+        if file_end > file_len as u64 {
+            return Err(ElfLoadError::SegmentOutOfBounds);
+        }
+        Ok(Self {
+            start_va: to_usize(vaddr)?,
+            end_va: to_usize(end_va)?,
+            file_end_va: to_usize(file_end_va)?,
+            offset: to_usize(ph.offset())?,
+            file_end: to_usize(file_end)?,
+        })
+    }
+}
+
+/// convert an address or a size of an elf to usize, checked
+pub(crate) fn to_usize(value: u64) -> Result<usize, ElfLoadError> {
+    usize::try_from(value).map_err(|_| ElfLoadError::AddressOverflow)
+}
+
+mod load_plan;
+#[cfg(feature = "symbols")]
+mod symbol_index;
+
+pub use load_plan::{plan, plan_with_policy, LoadPlan, PlannedArea};
+#[cfg(feature = "symbols")]
+pub use symbol_index::SymbolIndex;
@@ -0,0 +1,242 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+use page_table::{VirtAddr, PAGE_SIZE};
+use xmas_elf::{
+    program::{Flags, Type},
+    ElfFile,
+};
+
+use super::{to_usize, SegmentBounds, ELF_MAGIC};
+use crate::{
+    CloneStrategy, ElfLoadError, ElfLoadPolicy, LayoutError, MapPermission, MapType,
+    ProgramHeaders, ProgramLayout, SegmentClass, SegmentStrategy,
+};
+
+/// an area a load would map for a segment of the elf
+#[derive(Clone, Debug)]
+pub struct PlannedArea {
+    pub start_va: usize,
+    pub end_va: usize,
+    pub map_type: MapType,
+    pub perm: MapPermission,
+    pub clone_strategy: CloneStrategy,
+    /// index of the program header, the number of program headers for the copy of the
+    /// elf headers, see `LoadPlan::program_headers`
+    pub segment: usize,
+    /// bytes of the elf copied to the start of the area, if any
+    pub file_range: Option<Range<usize>>,
+}
+
+impl PlannedArea {
+    pub fn page_count(&self) -> usize {
+        page_count(self.start_va, self.end_va)
+    }
+}
+
+/// where a load would place everything, see `plan`
+#[derive(Clone, Debug)]
+pub struct LoadPlan {
+    /// areas of the segments, in the order of their program headers
+    pub areas: Vec<PlannedArea>,
+    /// bottom and top of the user stack, the top is the initial sp
+    pub user_stack: (usize, usize),
+    pub user_stack_type: MapType,
+    /// pages at the top of a lazy user stack populated at load
+    pub user_stack_prepopulated: usize,
+    /// see `MapArea::with_grows_down`
+    pub user_stack_growth: Option<(usize, usize)>,
+    pub heap_start: Option<usize>,
+    /// start and end of the trap context
+    pub trap_cx: (usize, usize),
+    pub trampoline_va: usize,
+    pub trampoline_pa: usize,
+    pub entry: usize,
+    /// within a segment if one maps them readable, otherwise in a copy of the elf headers
+    /// placed after the last segment
+    pub program_headers: ProgramHeaders,
+}
+
+impl LoadPlan {
+    /// frames the load takes, page table nodes and pages populated on demand aside
+    pub fn frames_needed(&self) -> usize {
+        let segments = self
+            .areas
+            .iter()
+            .filter(|area| area.map_type.is_framed())
+            .map(PlannedArea::page_count)
+            .sum::<usize>();
+        let stack_pages = page_count(self.user_stack.0, self.user_stack.1);
+        let stack = if self.user_stack_type.is_framed() {
+            stack_pages
+        } else {
+            self.user_stack_prepopulated.min(stack_pages)
+        };
+        segments + stack + page_count(self.trap_cx.0, self.trap_cx.1)
+    }
+}
+
+fn page_count(start_va: usize, end_va: usize) -> usize {
+    VirtAddr::from(end_va).ceil().0 - VirtAddr::from(start_va).floor().0
+}
+
+/// Compute where `MemorySet::from_elf` would place the segments of the elf, the user stack
+/// and the trap context, without touching a page table, e.g. to check quotas first.
+pub fn plan(elf_data: &[u8], layout: &ProgramLayout) -> Result<LoadPlan, ElfLoadError> {
+    plan_with_policy(elf_data, layout, &ElfLoadPolicy::default())
+}
+
+/// Same as `plan`, for `MemorySet::from_elf_with_policy`.
+pub fn plan_with_policy(
+    elf_data: &[u8],
+    layout: &ProgramLayout,
+    policy: &ElfLoadPolicy,
+) -> Result<LoadPlan, ElfLoadError> {
+    let elf = ElfFile::new(elf_data).map_err(ElfLoadError::Malformed)?;
+    if elf.header.pt1.magic != ELF_MAGIC {
+        return Err(ElfLoadError::BadMagic);
+    }
+    let mut areas = Vec::new();
+    let mut loaded = Vec::new();
+    let mut max_end_va = 0;
+    for i in 0..elf.header.pt2.ph_count() {
+        let ph = elf.program_header(i).map_err(ElfLoadError::Malformed)?;
+        if ph.get_type().map_err(ElfLoadError::Malformed)? != Type::Load {
+            continue;
+        }
+        let bounds = SegmentBounds::of(&ph, elf_data.len())?;
+        if bounds.end_va > layout.trap_cx_start_va {
+            return Err(ElfLoadError::Layout(LayoutError::Overflow));
+        }
+        max_end_va = VirtAddr::from(VirtAddr::from(bounds.end_va).ceil()).0;
+        let perm = map_perm(ph.flags());
+        loaded.push((bounds.offset, bounds.file_end, bounds.start_va, perm));
+        let class = if perm.contains(MapPermission::X) {
+            SegmentClass::Text
+        } else if perm.contains(MapPermission::W) {
+            SegmentClass::Data
+        } else {
+            SegmentClass::Rodata
+        };
+        let (map_type, clone_strategy) = policy.segment_strategy(class).file_backed();
+
+        // pages past the file image only hold bss, map large tails of them on demand
+        let bss_start = VirtAddr::from(VirtAddr::from(bounds.file_end_va).ceil()).0;
+        let bss_pages = page_count(bss_start, bounds.end_va);
+        let lazy_bss = class == SegmentClass::Data
+            && policy.segment_strategy(SegmentClass::Bss) == SegmentStrategy::LazyZero;
+        let end_va = match policy.lazy_bss_pages() {
+            Some(min_pages) if lazy_bss && bss_pages >= min_pages => {
+                areas.push(PlannedArea {
+                    start_va: bss_start,
+                    end_va: bounds.end_va,
+                    map_type: MapType::Lazy,
+                    perm,
+                    clone_strategy,
+                    segment: i as usize,
+                    file_range: None,
+                });
+                bounds.file_end_va
+            }
+            _ => bounds.end_va,
+        };
+        if page_count(bounds.start_va, end_va) == 0 {
+            continue;
+        }
+        areas.push(PlannedArea {
+            start_va: bounds.start_va,
+            end_va,
+            map_type,
+            perm,
+            clone_strategy,
+            segment: i as usize,
+            file_range: Some(bounds.offset..bounds.file_end),
+        });
+    }
+    let entry = to_usize(elf.header.pt2.entry_point())?;
+
+    // dynamic linkers read the program headers at AT_PHDR
+    let ph_offset = to_usize(elf.header.pt2.ph_offset())?;
+    let ph_entry_size = elf.header.pt2.ph_entry_size() as usize;
+    let ph_count = elf.header.pt2.ph_count() as usize;
+    let ph_end = ph_count
+        .checked_mul(ph_entry_size)
+        .and_then(|size| size.checked_add(ph_offset))
+        .filter(|end| *end <= elf_data.len())
+        .ok_or(ElfLoadError::SegmentOutOfBounds)?;
+    let covering = loaded.iter().find(|(offset, file_end, _, perm)| {
+        *offset <= ph_offset && ph_end <= *file_end && perm.contains(MapPermission::R)
+    });
+    let phdr_va = match covering {
+        Some((offset, _, start_va, _)) => start_va
+            .checked_add(ph_offset - offset)
+            .ok_or(ElfLoadError::AddressOverflow)?,
+        None => {
+            let start_va = max_end_va;
+            let end_va = start_va
+                .checked_add(ph_end)
+                .ok_or(ElfLoadError::Layout(LayoutError::Overflow))?;
+            areas.push(PlannedArea {
+                start_va,
+                end_va,
+                map_type: MapType::Framed,
+                perm: MapPermission::R | MapPermission::U,
+                clone_strategy: CloneStrategy::Share,
+                segment: ph_count,
+                file_range: Some(0..ph_end),
+            });
+            max_end_va = VirtAddr::from(VirtAddr::from(end_va).ceil()).0;
+            start_va + ph_offset
+        }
+    };
+
+    let user_stack_bottom = if policy.heap() {
+        // below the trap context, with a guard page
+        layout
+            .trap_cx_start_va
+            .checked_sub(PAGE_SIZE)
+            .and_then(|va| va.checked_sub(layout.user_stack_size))
+    } else {
+        // guard page
+        max_end_va.checked_add(PAGE_SIZE)
+    };
+    let user_stack = user_stack_bottom
+        .and_then(|bottom| Some((bottom, bottom.checked_add(layout.user_stack_size)?)))
+        .filter(|(bottom, top)| *bottom >= max_end_va && *top <= layout.trap_cx_start_va)
+        .ok_or(ElfLoadError::Layout(LayoutError::Overflow))?;
+    Ok(LoadPlan {
+        areas,
+        user_stack,
+        user_stack_type: if policy.lazy_stack().is_some() {
+            MapType::Lazy
+        } else {
+            MapType::Framed
+        },
+        user_stack_prepopulated: policy.lazy_stack().unwrap_or(0),
+        user_stack_growth: policy.stack_growth(),
+        heap_start: policy.heap().then_some(max_end_va),
+        trap_cx: (layout.trap_cx_start_va, layout.trampoline_va),
+        trampoline_va: layout.trampoline_va,
+        trampoline_pa: layout.trampoline_pa,
+        entry,
+        program_headers: ProgramHeaders {
+            va: phdr_va,
+            entry_size: ph_entry_size,
+            count: ph_count,
+        },
+    })
+}
+
+fn map_perm(ph_flags: Flags) -> MapPermission {
+    let mut map_perm = MapPermission::U;
+    if ph_flags.is_read() {
+        map_perm |= MapPermission::R;
+    }
+    if ph_flags.is_write() {
+        map_perm |= MapPermission::W;
+    }
+    if ph_flags.is_execute() {
+        map_perm |= MapPermission::X;
+    }
+
+    map_perm
+}
@@ -0,0 +1,53 @@
+use alloc::{string::String, vec::Vec};
+use xmas_elf::{
+    sections::SectionData,
+    symbol_table::{Entry, Type},
+    ElfFile,
+};
+
+/// function symbols of an elf sorted by address, to report faulting pcs as `function+offset`
+pub struct SymbolIndex {
+    /// start, size and name of each function
+    symbols: Vec<(u64, u64, String)>,
+}
+
+impl SymbolIndex {
+    /// `None` if the elf cannot be parsed or has no symbol table
+    pub fn from_elf(elf_data: &[u8]) -> Option<Self> {
+        let elf = ElfFile::new(elf_data).ok()?;
+        let section = elf.find_section_by_name(".symtab")?;
+        let SectionData::SymbolTable64(entries) = section.get_data(&elf).ok()? else {
+            return None;
+        };
+        let mut symbols = entries
+            .iter()
+            .filter(|entry| entry.get_type() == Ok(Type::Func) && entry.value() != 0)
+            .filter_map(|entry| {
+                let name = entry.get_name(&elf).ok()?;
+                Some((entry.value(), entry.size(), String::from(name)))
+            })
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|(start, _, _)| *start);
+        Some(Self { symbols })
+    }
+
+    /// function containing `pc` and the offset of `pc` in it
+    pub fn lookup(&self, pc: usize) -> Option<(&str, usize)> {
+        let pc = pc as u64;
+        let idx = self
+            .symbols
+            .partition_point(|(start, _, _)| *start <= pc)
+            .checked_sub(1)?;
+        let (start, size, name) = &self.symbols[idx];
+        // symbols without size, e.g. hand-written assembly, extend to the next one
+        (*size == 0 || pc < start + size).then_some((name.as_str(), (pc - start) as usize))
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{elf::ELF_MAGIC, BinFmt, ElfLoadPolicy, LoadResult, MemorySet, ProgramLayout};
+
+/// elf executables, loaded like `MemorySet::from_elf_with_policy`
+#[derive(Default)]
+pub struct ElfBinFmt {
+    policy: ElfLoadPolicy,
+}
+
+impl ElfBinFmt {
+    pub fn new(policy: ElfLoadPolicy) -> Self {
+        Self { policy }
+    }
+}
+
+impl BinFmt for ElfBinFmt {
+    fn probe(&self, data: &[u8]) -> bool {
+        data.starts_with(&ELF_MAGIC)
+    }
+
+    fn load(&self, memory_set: &mut MemorySet, data: &[u8], layout: &ProgramLayout) -> LoadResult {
+        match MemorySet::load_elf_into(memory_set, data, layout, None, &self.policy) {
+            Ok((user_sp, entry)) => LoadResult::Loaded { user_sp, entry },
+            Err(err) => LoadResult::Invalid(err),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use crate::{LayoutError, TransactionError};
+
+/// reason `MemorySet::from_elf` rejected an elf, nothing is mapped then
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ElfLoadError {
+    /// the headers could not be parsed
+    Malformed(&'static str),
+    /// the file does not start with the elf magic
+    BadMagic,
+    /// the file image of a segment lies outside of the file
+    SegmentOutOfBounds,
+    /// an address or a size does not fit the address space of the host
+    AddressOverflow,
+    /// the segments, the user stack and the trap context do not fit together
+    Layout(LayoutError),
+    /// the areas of a plan could not be mapped, e.g. for lack of frames,
+    /// see `MemorySet::apply_plan`
+    Map(TransactionError),
+}
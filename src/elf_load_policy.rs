@@ -0,0 +1,105 @@
+use crate::{default_segment_strategy, SegmentClass, SegmentStrategy};
+
+/// bss tails of at least this many pages are populated on demand, by default
+pub const DEFAULT_LAZY_BSS_PAGES: usize = 4;
+
+/// how `MemorySet::from_elf_with_policy` maps the segments of an elf
+#[derive(Copy, Clone, Debug)]
+pub struct ElfLoadPolicy {
+    /// minimum number of bss-only pages at the end of a writable segment to map them lazily
+    lazy_bss_pages: Option<usize>,
+    segment_strategy: fn(SegmentClass) -> SegmentStrategy,
+    /// allocate all frames of a segment before copying, then copy in ascending physical order
+    physical_order_copy: bool,
+    /// create a heap right after the last segment
+    heap: bool,
+    /// see `MapArea::with_grows_down`
+    stack_growth: Option<(usize, usize)>,
+    /// map the user stack lazily, except for this many pages at its top
+    lazy_stack: Option<usize>,
+}
+
+impl ElfLoadPolicy {
+    pub fn new() -> Self {
+        Self {
+            lazy_bss_pages: Some(DEFAULT_LAZY_BSS_PAGES),
+            segment_strategy: default_segment_strategy,
+            physical_order_copy: false,
+            heap: false,
+            stack_growth: None,
+            lazy_stack: None,
+        }
+    }
+
+    /// map bss tails of at least `pages` pages lazily, `None` maps and zeroes them at load
+    pub fn with_lazy_bss_pages(mut self, pages: Option<usize>) -> Self {
+        self.lazy_bss_pages = pages;
+        self
+    }
+
+    pub fn lazy_bss_pages(&self) -> Option<usize> {
+        self.lazy_bss_pages
+    }
+
+    /// Map the pages of each segment class as `strategy` says, `default_segment_strategy`
+    /// by default. Bss pages are mapped `LazyZero` only for tails of at least
+    /// `lazy_bss_pages`, and with their segment otherwise.
+    pub fn with_segment_strategy(mut self, strategy: fn(SegmentClass) -> SegmentStrategy) -> Self {
+        self.segment_strategy = strategy;
+        self
+    }
+
+    pub fn segment_strategy(&self, class: SegmentClass) -> SegmentStrategy {
+        (self.segment_strategy)(class)
+    }
+
+    /// Allocate all frames of a segment first and copy the data in ascending physical order,
+    /// so that the memory controller sees streaming writes instead of interleaved allocation.
+    pub fn with_physical_order_copy(mut self, physical_order_copy: bool) -> Self {
+        self.physical_order_copy = physical_order_copy;
+        self
+    }
+
+    pub fn physical_order_copy(&self) -> bool {
+        self.physical_order_copy
+    }
+
+    /// Create an empty heap right after the last segment, see `MemorySet::set_brk`. The user
+    /// stack moves below the trap context then, one guard page apart, to leave the heap room.
+    pub fn with_heap(mut self, heap: bool) -> Self {
+        self.heap = heap;
+        self
+    }
+
+    pub fn heap(&self) -> bool {
+        self.heap
+    }
+
+    /// Grow the user stack on faults at most `max_gap` pages below it, up to `max_pages`
+    /// pages, see `MapArea::with_grows_down`.
+    pub fn with_stack_growth(mut self, max_gap: usize, max_pages: usize) -> Self {
+        self.stack_growth = Some((max_gap, max_pages));
+        self
+    }
+
+    pub fn stack_growth(&self) -> Option<(usize, usize)> {
+        self.stack_growth
+    }
+
+    /// Map the user stack on demand, but populate its top `prepopulated_pages` pages at load,
+    /// so that runtimes probing deep below the initial sp do not take a burst of faults.
+    pub fn with_lazy_stack(mut self, prepopulated_pages: usize) -> Self {
+        self.lazy_stack = Some(prepopulated_pages);
+        self
+    }
+
+    pub fn lazy_stack(&self) -> Option<usize> {
+        self.lazy_stack
+    }
+}
+
+impl Default for ElfLoadPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
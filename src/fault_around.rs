@@ -0,0 +1,100 @@
+use alloc::collections::btree_set::BTreeSet;
+use page_table::{PTEFlags, PageTable, VPNRange, VirtPageNum};
+
+use crate::pte_ext;
+
+/// pages populated ahead of a fault in areas accessed sequentially, by default
+pub const DEFAULT_FAULT_AROUND_PAGES: usize = 8;
+
+/// window of fault-around and bookkeeping of the pages populated ahead of faults,
+/// a prefetched page counts as a fault avoided once the hardware marked it accessed
+pub struct FaultAround {
+    window: usize,
+    /// prefetched pages still mapped
+    prefetched: BTreeSet<VirtPageNum>,
+    prefetched_total: usize,
+    /// prefetched pages unmapped after being accessed
+    retired_accessed: usize,
+    /// prefetched pages unmapped without being accessed
+    retired_wasted: usize,
+}
+
+impl FaultAround {
+    pub fn new() -> Self {
+        Self {
+            window: DEFAULT_FAULT_AROUND_PAGES,
+            prefetched: BTreeSet::new(),
+            prefetched_total: 0,
+            retired_accessed: 0,
+            retired_wasted: 0,
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window;
+    }
+
+    pub fn record(&mut self, vpn: VirtPageNum) {
+        self.prefetched.insert(vpn);
+        self.prefetched_total += 1;
+    }
+
+    /// account the prefetched pages in `vpn_range`, which are about to be unmapped
+    pub fn retire(&mut self, page_table: &PageTable, vpn_range: VPNRange) {
+        let retired = self
+            .prefetched
+            .range(vpn_range.get_start()..vpn_range.get_end())
+            .copied()
+            .collect::<BTreeSet<_>>();
+        for vpn in retired {
+            self.prefetched.remove(&vpn);
+            if Self::accessed(page_table, vpn) {
+                self.retired_accessed += 1;
+            } else {
+                self.retired_wasted += 1;
+            }
+        }
+    }
+
+    /// account all prefetched pages, the whole address space is about to be unmapped
+    pub fn retire_all(&mut self, page_table: &PageTable) {
+        for vpn in core::mem::take(&mut self.prefetched) {
+            if Self::accessed(page_table, vpn) {
+                self.retired_accessed += 1;
+            } else {
+                self.retired_wasted += 1;
+            }
+        }
+    }
+
+    /// whether the page was prefetched and has not been accessed since
+    pub fn is_untouched(&self, page_table: &PageTable, vpn: VirtPageNum) -> bool {
+        self.prefetched.contains(&vpn) && !Self::accessed(page_table, vpn)
+    }
+
+    pub fn prefetched_total(&self) -> usize {
+        self.prefetched_total
+    }
+
+    pub fn faults_avoided(&self, page_table: &PageTable) -> usize {
+        self.retired_accessed
+            + self
+                .prefetched
+                .iter()
+                .filter(|vpn| Self::accessed(page_table, **vpn))
+                .count()
+    }
+
+    pub fn wasted(&self) -> usize {
+        self.retired_wasted
+    }
+
+    fn accessed(page_table: &PageTable, vpn: VirtPageNum) -> bool {
+        pte_ext::translate(page_table, vpn)
+            .is_some_and(|pte| pte.is_valid() && pte.flags().contains(PTEFlags::A))
+    }
+}
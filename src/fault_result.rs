@@ -0,0 +1,30 @@
+use page_table::VirtAddr;
+
+use crate::AccessType;
+
+/// outcome of `MemorySet::handle_page_fault`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FaultResult {
+    /// the page is mapped now, the faulting instruction can be retried
+    Resolved,
+    /// no area covers the faulting address
+    NotMapped,
+    /// the area covering the faulting address does not allow the access
+    PermissionDenied,
+    /// resolving the fault needs an allocation, which is not allowed in the current context,
+    /// see `MemorySet::handle_page_fault_nonalloc`
+    NeedsAllocation,
+    /// no frame is left to resolve the fault, even after running the reclaim callback,
+    /// see `MemorySet::set_reclaim_callback`
+    OutOfMemory,
+    /// resolving the fault needs a frame the scratch could not supply, retry from schedulable
+    /// context, see `MemorySet::try_handle_page_fault`
+    WouldBlock,
+    /// the page lies in a region registered by `MemorySet::register_userfault_region`,
+    /// the fault is left to whoever registered it, see `MemorySet::resolve_userfault`
+    Userfault {
+        token: usize,
+        va: VirtAddr,
+        kind: AccessType,
+    },
+}
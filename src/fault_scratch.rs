@@ -0,0 +1,43 @@
+use alloc::{sync::Arc, vec::Vec};
+use page_table::{frame_alloc, FrameTracker};
+
+/// Frames allocated ahead of time, so that `MemorySet::handle_page_fault_nonalloc`
+/// can resolve faults from the trap handler without touching the kernel heap.
+/// Kernels keep one per hart and refill it from schedulable context.
+pub struct FaultScratch {
+    frames: Vec<Arc<FrameTracker>>,
+    capacity: usize,
+}
+
+impl FaultScratch {
+    pub fn new(capacity: usize) -> Self {
+        let mut scratch = Self {
+            frames: Vec::with_capacity(capacity),
+            capacity,
+        };
+        scratch.refill();
+        scratch
+    }
+
+    /// top up the frames, this allocates and must not be invoked from the trap handler
+    pub fn refill(&mut self) {
+        while self.frames.len() < self.capacity {
+            match frame_alloc() {
+                Some(frame) => self.frames.push(Arc::new(frame)),
+                None => break,
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub(crate) fn take(&mut self) -> Option<Arc<FrameTracker>> {
+        self.frames.pop()
+    }
+}
@@ -0,0 +1,9 @@
+use page_table::{PhysPageNum, VirtPageNum};
+
+/// a kernel page mapped R+X at a fixed va besides the trampoline, see `MemorySet::map_fixed`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FixedMapping {
+    pub name: &'static str,
+    pub vpn: VirtPageNum,
+    pub ppn: PhysPageNum,
+}
@@ -0,0 +1,8 @@
+/// what `MemorySet::mmap_fixed` does about areas already in the range
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FixedMode {
+    /// unmap them first, splitting areas that stick out of the range, like `MAP_FIXED`
+    ReplaceExisting,
+    /// fail, like `MAP_FIXED_NOREPLACE`
+    FailOnOverlap,
+}
@@ -0,0 +1,158 @@
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
+use page_table::{FrameTracker, VPNRange, VirtPageNum};
+
+use crate::{swap_backend::SwapSlot, PageState};
+
+/// metadata of a page of an area
+pub struct PageInfo {
+    frame: Option<Arc<FrameTracker>>,
+    state: PageState,
+    /// where the content went while the page is swapped out
+    swap_slot: Option<SwapSlot>,
+}
+
+impl PageInfo {
+    pub fn frame(&self) -> Option<&Arc<FrameTracker>> {
+        self.frame.as_ref()
+    }
+
+    pub fn state(&self) -> PageState {
+        self.state
+    }
+
+    pub(crate) fn swap_slot(&self) -> Option<&SwapSlot> {
+        self.swap_slot.as_ref()
+    }
+
+    /// whether the frame is mapped by another area as well
+    pub fn is_shared(&self) -> bool {
+        self.frame
+            .as_ref()
+            .is_some_and(|frame| Arc::strong_count(frame) > 1)
+    }
+
+    /// move to `state`, invalid transitions are caught in debug builds
+    fn transition(&mut self, state: PageState) {
+        debug_assert!(
+            self.state.can_transition_to(state),
+            "invalid page state transition {:?} -> {:?}",
+            self.state,
+            state
+        );
+        self.state = state;
+    }
+}
+
+#[derive(Default)]
+/// frames backing the pages of the map areas of a memory set, keyed by page since areas
+/// never overlap, so that per-page metadata lives in one place and splitting or merging
+/// areas leaves the table alone
+pub struct FrameTable {
+    pages: BTreeMap<VirtPageNum, PageInfo>,
+    /// page table nodes allocated outside of the page table, see `pte_ext::map_huge`
+    tables: Vec<FrameTracker>,
+}
+
+impl FrameTable {
+    pub fn new() -> Self {
+        Self {
+            pages: BTreeMap::new(),
+            tables: Vec::new(),
+        }
+    }
+
+    pub(crate) fn tables_mut(&mut self) -> &mut Vec<FrameTracker> {
+        &mut self.tables
+    }
+
+    pub fn get(&self, vpn: VirtPageNum) -> Option<&PageInfo> {
+        self.pages.get(&vpn)
+    }
+
+    pub(crate) fn get_mut(&mut self, vpn: VirtPageNum) -> Option<&mut PageInfo> {
+        self.pages.get_mut(&vpn)
+    }
+
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.pages.contains_key(&vpn)
+    }
+
+    /// back a page by a frame, the page moves from `Unallocated` to `state`
+    pub(crate) fn insert(&mut self, vpn: VirtPageNum, frame: Arc<FrameTracker>, state: PageState) {
+        let mut info = PageInfo {
+            frame: Some(frame),
+            state: PageState::Unallocated,
+            swap_slot: None,
+        };
+        info.transition(state);
+        self.pages.insert(vpn, info);
+    }
+
+    /// state of a page, pages not in the table are `Unallocated`
+    pub fn state(&self, vpn: VirtPageNum) -> PageState {
+        self.get(vpn)
+            .map_or(PageState::Unallocated, |info| info.state)
+    }
+
+    pub(crate) fn set_state(&mut self, vpn: VirtPageNum, state: PageState) {
+        if let Some(info) = self.get_mut(vpn) {
+            info.transition(state);
+        }
+    }
+
+    /// back a page by another frame and move it to `state`, returns the previous frame
+    pub(crate) fn replace_frame(
+        &mut self,
+        vpn: VirtPageNum,
+        frame: Arc<FrameTracker>,
+        state: PageState,
+    ) -> Option<Arc<FrameTracker>> {
+        let info = self.get_mut(vpn)?;
+        info.transition(state);
+        info.frame.replace(frame)
+    }
+
+    /// the content of a page went to `slot`, the page drops its frame and is `SwappedOut`
+    pub(crate) fn swap_out(&mut self, vpn: VirtPageNum, slot: SwapSlot) {
+        if let Some(info) = self.get_mut(vpn) {
+            info.transition(PageState::SwappedOut);
+            info.frame = None;
+            info.swap_slot = Some(slot);
+        }
+    }
+
+    /// the content of a swapped out page was read into `frame`, its slot is freed
+    pub(crate) fn swap_in(&mut self, vpn: VirtPageNum, frame: Arc<FrameTracker>) {
+        if let Some(info) = self.get_mut(vpn) {
+            info.transition(PageState::Resident);
+            info.frame = Some(frame);
+            info.swap_slot = None;
+        }
+    }
+
+    /// key a page by another page, its state is kept, e.g. when its area moves
+    pub(crate) fn rekey(&mut self, from: VirtPageNum, to: VirtPageNum) {
+        if let Some(info) = self.pages.remove(&from) {
+            self.pages.insert(to, info);
+        }
+    }
+
+    /// forget a page, it moves to `Unallocated`
+    pub(crate) fn remove(&mut self, vpn: VirtPageNum) -> Option<PageInfo> {
+        let mut info = self.pages.remove(&vpn)?;
+        info.transition(PageState::Unallocated);
+        Some(info)
+    }
+
+    /// pages of `vpn_range` backed by frames, in ascending order
+    pub fn pages(&self, vpn_range: VPNRange) -> impl Iterator<Item = (VirtPageNum, &PageInfo)> {
+        self.pages
+            .range(vpn_range.get_start()..vpn_range.get_end())
+            .map(|(vpn, info)| (*vpn, info))
+    }
+
+    /// number of pages of `vpn_range` backed by frames
+    pub fn count(&self, vpn_range: VPNRange) -> usize {
+        self.pages(vpn_range).count()
+    }
+}
@@ -0,0 +1,56 @@
+//! privileged register access, stubbed out without the `riscv-hw` feature
+//! so that host tools can link the crate
+
+/// switch to `token`, flushing the whole tlb if `asid` is 0 and the entries of `asid`
+/// if they are `stale`
+#[cfg(feature = "riscv-hw")]
+pub fn activate(token: usize, asid: usize, stale: bool) {
+    use core::arch::asm;
+    use riscv::register::satp;
+
+    unsafe {
+        satp::write(token);
+        if asid == 0 {
+            asm!("sfence.vma");
+        } else if stale {
+            asm!("sfence.vma zero, {}", in(reg) asid);
+        }
+    }
+}
+
+#[cfg(not(feature = "riscv-hw"))]
+pub fn activate(_token: usize, _asid: usize, _stale: bool) {}
+
+/// flush the tlb entries of a page of all address spaces on the calling hart
+#[cfg(feature = "riscv-hw")]
+pub fn flush_page(vpn: page_table::VirtPageNum) {
+    let va = page_table::VirtAddr::from(vpn).0;
+    unsafe {
+        core::arch::asm!("sfence.vma {}, zero", in(reg) va);
+    }
+}
+
+#[cfg(not(feature = "riscv-hw"))]
+pub fn flush_page(_vpn: page_table::VirtPageNum) {}
+
+/// flush the tlb entries tagged with `asid` on the calling hart
+#[cfg(feature = "riscv-hw")]
+pub fn flush_asid(asid: usize) {
+    unsafe {
+        core::arch::asm!("sfence.vma zero, {}", in(reg) asid);
+    }
+}
+
+#[cfg(not(feature = "riscv-hw"))]
+pub fn flush_asid(_asid: usize) {}
+
+/// flush the tlb of the calling hart
+#[cfg(feature = "riscv-hw")]
+pub fn flush_tlb() {
+    unsafe {
+        core::arch::asm!("sfence.vma");
+    }
+}
+
+#[cfg(not(feature = "riscv-hw"))]
+pub fn flush_tlb() {}
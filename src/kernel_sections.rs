@@ -0,0 +1,80 @@
+use alloc::vec::Vec;
+use page_table::{VirtAddr, PAGE_SIZE};
+
+use crate::MapPermission;
+
+/// The sections of a kernel image, e.g. `.text` between the linker symbols `stext` and
+/// `etext`, and the device windows to map after them, see
+/// `MemorySetBuilder::push_kernel_sections`.
+#[derive(Default)]
+pub struct KernelSections {
+    sections: Vec<(&'static str, usize, usize, MapPermission)>,
+    /// name, physical address, length and permission of each device window
+    mmio: Vec<(&'static str, usize, usize, MapPermission)>,
+}
+
+impl KernelSections {
+    pub fn new() -> Self {
+        Self {
+            sections: Vec::new(),
+            mmio: Vec::new(),
+        }
+    }
+
+    /// a section mapped identically at `start..end`
+    pub fn with_section(
+        mut self,
+        name: &'static str,
+        start: usize,
+        end: usize,
+        perm: MapPermission,
+    ) -> Self {
+        self.sections.push((name, start, end, perm));
+        self
+    }
+
+    /// a device window of `len` bytes at `pa`, mapped after the sections, see `MapType::Mmio`
+    pub fn with_mmio(
+        mut self,
+        name: &'static str,
+        pa: usize,
+        len: usize,
+        perm: MapPermission,
+    ) -> Self {
+        self.mmio.push((name, pa, len, perm));
+        self
+    }
+
+    /// Page-aligned name, start, end and permission of every range as it will be mapped.
+    /// Device windows follow the highest section in the order they were added, without U.
+    /// Build systems can compare it with the linker script at test time.
+    pub fn export_layout(&self) -> Vec<(&'static str, usize, usize, MapPermission)> {
+        let mut layout = self
+            .sections
+            .iter()
+            .map(|&(name, start, end, perm)| {
+                let start: VirtAddr = VirtAddr::from(start).floor().into();
+                let end: VirtAddr = VirtAddr::from(end).ceil().into();
+                (name, start.0, end.0, perm)
+            })
+            .collect::<Vec<_>>();
+
+        let mut next = layout.iter().map(|&(_, _, end, _)| end).max().unwrap_or(0);
+        for &(name, pa, len, perm) in &self.mmio {
+            let pages = VirtAddr::from((pa & (PAGE_SIZE - 1)) + len).ceil().0;
+            layout.push((
+                name,
+                next,
+                next + pages * PAGE_SIZE,
+                perm - MapPermission::U,
+            ));
+            next += pages * PAGE_SIZE;
+        }
+        layout
+    }
+
+    /// physical address of each device window, in the order of `export_layout`
+    pub(crate) fn mmio_addresses(&self) -> impl Iterator<Item = usize> + '_ {
+        self.mmio.iter().map(|&(_, pa, _, _)| pa)
+    }
+}
@@ -0,0 +1,96 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use page_table::{PhysAddr, VirtAddr};
+
+use crate::{ElfLoadError, MemorySet};
+
+/// A kernel address space with its own trampoline, so that several of them with distinct
+/// mappings can coexist, e.g. one per isolation domain, each active on some of the harts.
+pub struct KernelSpace {
+    memory_set: MemorySet,
+    trampoline_va: usize,
+    trampoline_pa: usize,
+    /// bit `i` is set while hart `i` runs on the space
+    active_harts: AtomicUsize,
+}
+
+impl KernelSpace {
+    /// the space of `memory_set`, the trampoline is mapped into it
+    pub fn new(mut memory_set: MemorySet, trampoline_va: usize, trampoline_pa: usize) -> Self {
+        memory_set.map_trampoline(
+            VirtAddr::from(trampoline_va).into(),
+            PhysAddr::from(trampoline_pa).into(),
+        );
+        Self {
+            memory_set,
+            trampoline_va,
+            trampoline_pa,
+            active_harts: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn memory_set(&self) -> &MemorySet {
+        &self.memory_set
+    }
+
+    pub fn memory_set_mut(&mut self) -> &mut MemorySet {
+        &mut self.memory_set
+    }
+
+    pub fn token(&self) -> usize {
+        self.memory_set.token()
+    }
+
+    /// virtual and physical address of the trampoline of the space
+    pub fn trampoline(&self) -> (usize, usize) {
+        (self.trampoline_va, self.trampoline_pa)
+    }
+
+    /// switch the calling hart `hart_id` to the space,
+    /// the space it ran on before has to be left by `deactivate`,
+    /// panics if `hart_id` does not fit in the hart mask
+    pub fn activate(&self, hart_id: usize) {
+        self.active_harts
+            .fetch_or(hart_bit(hart_id), Ordering::AcqRel);
+        self.memory_set.activate(hart_id);
+    }
+
+    pub fn deactivate(&self, hart_id: usize) {
+        self.active_harts
+            .fetch_and(!hart_bit(hart_id), Ordering::AcqRel);
+    }
+
+    /// harts running on the space, those whose tlb has to be flushed after changes
+    pub fn active_harts(&self) -> usize {
+        self.active_harts.load(Ordering::Acquire)
+    }
+
+    /// Load a user program sharing the trampoline of the space, see `MemorySet::from_elf`.
+    pub fn user_space_from_elf(
+        &self,
+        elf_data: &[u8],
+        trap_cx_start_va: usize,
+        user_stack_size: usize,
+    ) -> Result<(MemorySet, usize, usize), ElfLoadError> {
+        MemorySet::from_elf(
+            elf_data,
+            self.trampoline_va,
+            self.trampoline_pa,
+            trap_cx_start_va,
+            user_stack_size,
+        )
+    }
+
+    /// Clone a user space of the space, see `MemorySet::from_existed_user`.
+    pub fn clone_user_space(&self, user_space: &mut MemorySet) -> Option<MemorySet> {
+        MemorySet::from_existed_user(user_space, self.trampoline_va, self.trampoline_pa)
+    }
+}
+
+/// the bit of `hart_id` in the hart mask
+fn hart_bit(hart_id: usize) -> usize {
+    assert!(
+        hart_id < usize::BITS as usize,
+        "hart id {hart_id} out of the hart mask"
+    );
+    1 << hart_id
+}
@@ -0,0 +1,6 @@
+/// reason the layout of a program does not fit its address space
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LayoutError {
+    /// an address computation wraps around, or runs into the trap context and the trampoline
+    Overflow,
+}
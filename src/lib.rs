@@ -1,15 +1,130 @@
 #![no_std]
 
+mod access_hint;
+mod access_type;
+mod activate_error;
+mod address_explanation;
+mod alloc_policy;
+mod area_class;
+mod area_handle;
+mod area_origin;
+mod asid_allocator;
+mod bin_fmt;
+mod canary;
+mod checkpoint;
+mod clock;
+mod clone_strategy;
+mod delegated_region;
+pub mod elf;
+mod elf_bin_fmt;
+mod elf_load_error;
+mod elf_load_policy;
+mod fault_around;
+mod fault_result;
+mod fault_scratch;
+mod fixed_mapping;
+mod fixed_mode;
+mod frame_table;
+mod hw;
+mod kernel_sections;
+mod kernel_space;
+mod layout_error;
+mod load_result;
 mod map_permission;
 mod map_type;
 mod memory_area;
 mod memory_set;
 mod memory_set_builder;
+mod memory_stats;
+mod overcommit_policy;
+mod overlap_policy;
+mod page_access_error;
+mod page_state;
+mod page_table_usage;
+mod phase;
+mod physical_overlap;
+mod program_headers;
+mod program_layout;
+mod pte_ext;
+mod reclaim_driver;
+mod reclaim_progress;
+pub mod registry;
+mod segment_strategy;
+mod shebang_bin_fmt;
+mod swap_backend;
+mod teardown;
+mod temp_area_guard;
+mod text_segment_registry;
+pub mod tls;
+mod transaction;
+mod transaction_error;
+mod translation;
+mod userfault_resolution;
+mod va_buddy;
+mod violation;
+mod vm_policy;
+mod window;
+mod wire;
+mod zeroed_pool;
 
+pub use access_hint::AccessHint;
+pub use access_type::AccessType;
+pub use activate_error::ActivateError;
+pub use address_explanation::{AddressExplanation, AreaSummary, Backing};
+pub use alloc_policy::AllocPolicy;
+pub use area_class::AreaClass;
+pub use area_handle::AreaHandle;
+pub use area_origin::AreaOrigin;
+pub use asid_allocator::{AsidAllocator, MAX_ASID_BITS};
+pub use bin_fmt::BinFmt;
+pub use canary::Canary;
+pub use checkpoint::CheckpointError;
+pub use clone_strategy::{CloneStrategy, DEFAULT_EAGER_COPY_PAGES};
+pub use delegated_region::DelegatedRegion;
+pub use elf_bin_fmt::ElfBinFmt;
+pub use elf_load_error::ElfLoadError;
+pub use elf_load_policy::{ElfLoadPolicy, DEFAULT_LAZY_BSS_PAGES};
+pub use fault_around::DEFAULT_FAULT_AROUND_PAGES;
+pub use fault_result::FaultResult;
+pub use fault_scratch::FaultScratch;
+pub use fixed_mapping::FixedMapping;
+pub use fixed_mode::FixedMode;
+pub use frame_table::{FrameTable, PageInfo};
+pub use kernel_sections::KernelSections;
+pub use kernel_space::KernelSpace;
+pub use layout_error::LayoutError;
+pub use load_result::LoadResult;
 pub use map_permission::MapPermission;
 pub use map_type::MapType;
 pub use memory_area::MapArea;
 pub use memory_set::MemorySet;
 pub use memory_set_builder::MemorySetBuilder;
+pub use memory_stats::MemoryStats;
+pub use overcommit_policy::OvercommitPolicy;
+pub use overlap_policy::OverlapPolicy;
+pub use page_access_error::PageAccessError;
+pub use page_state::PageState;
+pub use phase::Phase;
+pub use physical_overlap::PhysicalOverlap;
+pub use program_headers::ProgramHeaders;
+pub use program_layout::ProgramLayout;
+pub use reclaim_driver::ReclaimDriver;
+pub use reclaim_progress::ReclaimProgress;
+pub use segment_strategy::{default_segment_strategy, SegmentClass, SegmentStrategy};
+pub use shebang_bin_fmt::ShebangBinFmt;
+pub use swap_backend::SwapBackend;
+pub use teardown::Teardown;
+pub use temp_area_guard::TempAreaGuard;
+pub use text_segment_registry::{TextSegmentKey, TextSegmentRegistry};
+pub use transaction::Transaction;
+pub use transaction_error::TransactionError;
+pub use translation::Translation;
+pub use userfault_resolution::UserfaultResolution;
+pub use va_buddy::VaBuddy;
+pub use violation::Violation;
+pub use vm_policy::VmPolicy;
+pub use window::Window;
+pub use wire::WIRE_VERSION;
+pub use zeroed_pool::ZeroedPool;
 
 extern crate alloc;
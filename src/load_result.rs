@@ -0,0 +1,15 @@
+use alloc::string::String;
+
+use crate::ElfLoadError;
+
+/// outcome of `BinFmt::load`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LoadResult {
+    /// the program is mapped and can be entered
+    Loaded { user_sp: usize, entry: usize },
+    /// the program is a script, the interpreter at `path` has to be loaded instead with
+    /// `arg`, if any, and the path of the script prepended to argv
+    NeedsInterpreter { path: String, arg: Option<String> },
+    /// the program is malformed, nothing was mapped
+    Invalid(ElfLoadError),
+}
@@ -9,3 +9,35 @@ bitflags! {
         const U = 1 << 4;
     }
 }
+
+/// stable bits of `MapPermission` on the wire, independent of the pte layout
+const WIRE_BITS: [(MapPermission, u8); 4] = [
+    (MapPermission::R, 1 << 0),
+    (MapPermission::W, 1 << 1),
+    (MapPermission::X, 1 << 2),
+    (MapPermission::U, 1 << 3),
+];
+
+impl MapPermission {
+    /// stable code for on-disk formats, see `WIRE_VERSION`
+    pub fn to_wire(self) -> u8 {
+        WIRE_BITS
+            .iter()
+            .filter(|(perm, _)| self.contains(*perm))
+            .fold(0, |code, (_, bit)| code | bit)
+    }
+
+    /// `None` if `code` has unknown bits set
+    pub fn from_wire(code: u8) -> Option<Self> {
+        let known = WIRE_BITS.iter().fold(0, |known, (_, bit)| known | bit);
+        if code & !known != 0 {
+            return None;
+        }
+        Some(
+            WIRE_BITS
+                .iter()
+                .filter(|(_, bit)| code & bit != 0)
+                .fold(MapPermission::empty(), |perm, (p, _)| perm | *p),
+        )
+    }
+}
@@ -1,6 +1,49 @@
 #[derive(Copy, Clone, PartialEq, Debug)]
-/// map type for memory set: identical or framed
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// map type for memory set: identical, framed, lazily framed, shared or device memory
 pub enum MapType {
     Identical,
     Framed,
+    /// framed, but frames are allocated on the first access, see `MemorySet::handle_page_fault`
+    Lazy,
+    /// framed, but the frames are reference-counted and always shared instead of copied,
+    /// so that other memory sets can map them too, see `MemorySet::map_shared`
+    Shared,
+    /// a physical device range mapped at a chosen va, never user-accessible,
+    /// see `MapArea::new_mmio`
+    Mmio,
+}
+
+impl MapType {
+    /// whether every page is backed by a frame as soon as the area is mapped
+    pub fn is_framed(self) -> bool {
+        matches!(self, MapType::Framed | MapType::Shared)
+    }
+
+    /// whether the area maps physical memory fixed up front instead of frames
+    pub fn is_direct(self) -> bool {
+        matches!(self, MapType::Identical | MapType::Mmio)
+    }
+
+    /// stable code for on-disk formats, see `WIRE_VERSION`
+    pub fn to_wire(self) -> u8 {
+        match self {
+            MapType::Identical => 0,
+            MapType::Framed => 1,
+            MapType::Lazy => 2,
+            MapType::Shared => 3,
+            MapType::Mmio => 4,
+        }
+    }
+
+    pub fn from_wire(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(MapType::Identical),
+            1 => Some(MapType::Framed),
+            2 => Some(MapType::Lazy),
+            3 => Some(MapType::Shared),
+            4 => Some(MapType::Mmio),
+            _ => None,
+        }
+    }
 }
@@ -1,17 +1,84 @@
-use alloc::collections::btree_map::BTreeMap;
+use alloc::{string::String, sync::Arc, vec::Vec};
 use page_table::{
-    frame_alloc, FrameTracker, PTEFlags, PageTable, PhysPageNum, StepByOne, VPNRange, VirtAddr,
-    VirtPageNum, PAGE_SIZE,
+    frame_alloc, FrameTracker, PTEFlags, PageTable, PhysAddr, PhysPageNum, StepByOne, VPNRange,
+    VirtAddr, VirtPageNum, PAGE_SIZE,
 };
 
-use super::{map_permission::MapPermission, map_type::MapType};
+use super::{
+    access_hint::AccessHint, access_type::AccessType, clone_strategy::CloneStrategy,
+    fault_around::FaultAround, map_permission::MapPermission, map_type::MapType, pte_ext,
+    AreaClass, AreaHandle, AreaOrigin, FrameTable, PageState, Phase, ZeroedPool,
+};
+use spin::Mutex;
+
+/// frame mapped read-only by the untouched pages of all areas `with_zero_page`
+static ZERO_FRAME: Mutex<Option<Arc<FrameTracker>>> = Mutex::new(None);
+
+/// the zero frame, allocated on first use
+fn zero_frame() -> Option<Arc<FrameTracker>> {
+    let mut zero_frame = ZERO_FRAME.lock();
+    if zero_frame.is_none() {
+        *zero_frame = frame_alloc().map(Arc::new);
+    }
+    zero_frame.clone()
+}
+
+/// `MEGAPAGE_PAGES` contiguous frames aligned to their size, if the allocator hands them out
+/// in order, `None` otherwise
+fn alloc_megapage_frames() -> Option<Vec<FrameTracker>> {
+    let mut run: Vec<FrameTracker> = Vec::with_capacity(pte_ext::MEGAPAGE_PAGES);
+    // held until the run is complete or abandoned, freed they would be handed out again
+    let mut skipped = Vec::new();
+    for _ in 0..2 * pte_ext::MEGAPAGE_PAGES {
+        let frame = frame_alloc()?;
+        match run.last() {
+            Some(last) if frame.ppn.0 != last.ppn.0 + 1 => return None,
+            None if frame.ppn.0 & (pte_ext::MEGAPAGE_PAGES - 1) != 0 => skipped.push(frame),
+            _ => run.push(frame),
+        }
+        if run.len() == pte_ext::MEGAPAGE_PAGES {
+            return Some(run);
+        }
+    }
+    None
+}
 
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     pub vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// identifies the area, e.g. to mirror it into another memory set
+    handle: AreaHandle,
     map_type: MapType,
     map_perm: MapPermission,
+    clone_strategy: CloneStrategy,
+    /// map writable pages read-only until the first write, see `MemorySet::set_write_notifier`
+    write_notify: bool,
+    /// number of cache colors consecutive pages are spread across
+    page_colors: Option<usize>,
+    /// frames allocated while searching for a color, reused before allocating again
+    spare_frames: Vec<FrameTracker>,
+    access_hint: AccessHint,
+    /// rsw bits of the ptes of newly mapped pages
+    software_bits: u8,
+    /// permissions dropped when the memory set reaches a phase
+    transitions: Vec<(Phase, MapPermission)>,
+    origin: Option<AreaOrigin>,
+    /// name grouping areas for bulk operations, e.g. everything a thread owns
+    tag: Option<String>,
+    /// class set explicitly, inferred from U otherwise
+    class: Option<AreaClass>,
+    /// the area is a stack, see `MemorySet::stack_bounds_for`
+    stack: bool,
+    /// pages below the start a fault may lie to grow the area, and its maximum size in pages
+    grows_down: Option<(usize, usize)>,
+    /// the area survives `MemorySet::replace_from_elf`
+    keep_on_exec: bool,
+    /// map aligned runs of pages as megapages, see `with_huge_pages`
+    huge_pages: bool,
+    /// map read faults to the shared zero frame, see `with_zero_page`
+    zero_page: bool,
+    /// physical page of the first page of an mmio area
+    phys_start: Option<PhysPageNum>,
 }
 
 impl MapArea {
@@ -25,81 +92,986 @@ impl MapArea {
         let end_vpn: VirtPageNum = end_va.ceil();
         Self {
             vpn_range: VPNRange::new(start_vpn, end_vpn),
-            data_frames: BTreeMap::new(),
+            handle: AreaHandle::new(),
             map_type,
             map_perm,
+            clone_strategy: if map_type == MapType::Shared {
+                CloneStrategy::Share
+            } else {
+                CloneStrategy::default()
+            },
+            write_notify: false,
+            page_colors: None,
+            spare_frames: Vec::new(),
+            access_hint: AccessHint::default(),
+            software_bits: 0,
+            transitions: Vec::new(),
+            origin: None,
+            tag: None,
+            class: None,
+            stack: false,
+            grows_down: None,
+            keep_on_exec: false,
+            huge_pages: false,
+            zero_page: false,
+            phys_start: None,
         }
     }
 
+    /// Map the device memory at `pa` at `start_va..end_va`, without allocating frames.
+    /// The area is never user-accessible, U is dropped from `map_perm`.
+    pub fn new_mmio(
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        pa: PhysAddr,
+        map_perm: MapPermission,
+    ) -> Self {
+        let mut area = Self::new(start_va, end_va, MapType::Mmio, map_perm - MapPermission::U)
+            .with_class(AreaClass::Kernel);
+        area.phys_start = Some(pa.floor());
+        area
+    }
+
+    pub fn handle(&self) -> AreaHandle {
+        self.handle
+    }
+
+    pub fn with_clone_strategy(mut self, clone_strategy: CloneStrategy) -> Self {
+        self.clone_strategy = clone_strategy;
+        self
+    }
+
+    pub(crate) fn set_clone_strategy(&mut self, clone_strategy: CloneStrategy) {
+        self.clone_strategy = clone_strategy;
+    }
+
     pub fn from_another(another: &Self) -> Self {
         Self {
             vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
-            data_frames: BTreeMap::new(),
+            handle: AreaHandle::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            clone_strategy: another.clone_strategy,
+            write_notify: another.write_notify,
+            page_colors: another.page_colors,
+            spare_frames: Vec::new(),
+            access_hint: another.access_hint,
+            software_bits: another.software_bits,
+            transitions: another.transitions.clone(),
+            origin: another.origin,
+            tag: another.tag.clone(),
+            class: another.class,
+            stack: another.stack,
+            grows_down: another.grows_down,
+            keep_on_exec: another.keep_on_exec,
+            huge_pages: another.huge_pages,
+            zero_page: another.zero_page,
+            phys_start: another.phys_start,
+        }
+    }
+
+    /// Split the area at `at`, the pages from `at` on move to the returned area together with
+    /// their frames, which stay where they are in the frame table. Nothing is remapped.
+    pub(crate) fn split_off(&mut self, at: VirtPageNum) -> Self {
+        assert!(self.vpn_range.get_start() < at && at < self.vpn_range.get_end());
+        let mut tail = Self::from_another(self);
+        tail.vpn_range = VPNRange::new(at, self.vpn_range.get_end());
+        tail.phys_start = self
+            .phys_start
+            .map(|ppn| PhysPageNum(ppn.0 + at.0 - self.vpn_range.get_start().0));
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        tail
+    }
+
+    /// whether `next` starts where the area ends and differs in nothing but its range,
+    /// so that `merge` loses nothing
+    pub(crate) fn can_merge(&self, next: &Self) -> bool {
+        self.vpn_range.get_end() == next.vpn_range.get_start()
+            && self.map_type == next.map_type
+            && self.map_perm == next.map_perm
+            && self.clone_strategy == next.clone_strategy
+            && self.write_notify == next.write_notify
+            && self.page_colors == next.page_colors
+            && self.access_hint == next.access_hint
+            && self.software_bits == next.software_bits
+            && self.transitions == next.transitions
+            && self.origin == next.origin
+            && self.tag == next.tag
+            && self.class() == next.class()
+            && self.stack == next.stack
+            && self.grows_down == next.grows_down
+            && self.keep_on_exec == next.keep_on_exec
+            && self.huge_pages == next.huge_pages
+            && self.zero_page == next.zero_page
+            && self.phys_start.map(|ppn| ppn.0 + self.page_count())
+                == next.phys_start.map(|ppn| ppn.0)
+    }
+
+    /// Undo `split_off`, the pages of `next` move to the area together with their frames.
+    /// Nothing is remapped.
+    pub(crate) fn merge(&mut self, next: Self) {
+        debug_assert!(self.can_merge(&next));
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), next.vpn_range.get_end());
+    }
+
+    /// map the area read-only even if it is writable, so that the first write to each page faults
+    pub fn with_write_notify(mut self) -> Self {
+        self.write_notify = true;
+        self
+    }
+
+    pub fn write_notify(&self) -> bool {
+        self.write_notify
+    }
+
+    /// Back page `vpn` with a frame whose color `ppn % colors` equals `vpn % colors`, so that
+    /// consecutive pages do not compete for the same cache sets. `colors` is the cache size
+    /// divided by ways and page size. The color is best effort, see `MemoryStats::color_misses`.
+    pub fn with_page_coloring(mut self, colors: usize) -> Self {
+        assert!(colors > 0);
+        self.page_colors = Some(colors);
+        self
+    }
+
+    pub fn page_colors(&self) -> Option<usize> {
+        self.page_colors
+    }
+
+    /// number of frames whose color matches their page and number of frames whose color does not
+    pub fn color_hits_and_misses(&self, frames: &FrameTable) -> (usize, usize) {
+        match self.page_colors {
+            Some(colors) => {
+                let hits = frames
+                    .pages(self.vpn_range)
+                    .filter(|(vpn, info)| {
+                        info.frame()
+                            .is_some_and(|frame| frame.ppn.0 % colors == vpn.0 % colors)
+                    })
+                    .count();
+                (hits, frames.count(self.vpn_range) - hits)
+            }
+            None => (0, 0),
+        }
+    }
+
+    pub fn with_access_hint(mut self, access_hint: AccessHint) -> Self {
+        self.access_hint = access_hint;
+        self
+    }
+
+    pub fn access_hint(&self) -> AccessHint {
+        self.access_hint
+    }
+
+    /// Set the two rsw bits of every pte of the area, they are left to the kernel and
+    /// ignored by the hardware. Single pages can be overridden by `MemorySet::set_software_bits`.
+    pub fn with_software_bits(mut self, bits: u8) -> Self {
+        assert!(bits <= 0b11, "only two software bits in a pte");
+        self.software_bits = bits;
+        self
+    }
+
+    pub fn software_bits(&self) -> u8 {
+        self.software_bits
+    }
+
+    /// drop `perm` once the memory set reaches `phase`, see `MemorySet::advance_phase`
+    pub fn with_transition(mut self, phase: Phase, perm: MapPermission) -> Self {
+        self.transitions.push((phase, perm));
+        self
+    }
+
+    /// apply and forget the transitions declared for `phase`
+    pub(crate) fn advance_phase(&mut self, page_table: &mut PageTable, phase: Phase) {
+        let mut dropped = MapPermission::empty();
+        self.transitions.retain(|(p, perm)| {
+            if *p == phase {
+                dropped |= *perm;
+            }
+            *p != phase
+        });
+        if !dropped.is_empty() {
+            self.remove_permission(page_table, dropped);
+        }
+    }
+
+    /// drop `perm` from the area and its mapped pages, the class stays what it was
+    pub fn remove_permission(&mut self, page_table: &mut PageTable, perm: MapPermission) {
+        self.class = Some(self.class());
+        self.map_perm.remove(perm);
+        let removed = PTEFlags::from_bits(perm.bits()).unwrap();
+        for vpn in self.vpn_range {
+            if let Some(pte) = pte_ext::translate(page_table, vpn).filter(|pte| pte.is_valid()) {
+                pte_ext::remap(page_table, vpn, pte.ppn(), pte.flags() - removed);
+            }
         }
     }
 
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+    pub fn with_origin(mut self, origin: AreaOrigin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    pub fn origin(&self) -> Option<AreaOrigin> {
+        self.origin
+    }
+
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tag = Some(String::from(tag));
+        self
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// Replace the permission of the area. Mapped pages that are write-protected,
+    /// e.g. copy-on-write, stay so until the next write fault. The class stays what it was,
+    /// even if it was inferred from the old permission.
+    pub fn set_permission(&mut self, page_table: &mut PageTable, perm: MapPermission) {
+        self.class = Some(self.class());
+        self.map_perm = perm;
+        let pte_flags = self.pte_flags();
+        let mut vpn = self.vpn_range.get_start();
+        while vpn < self.vpn_range.get_end() {
+            if self.megapage_at(vpn) == Some(vpn) && pte_ext::huge_leaf(page_table, vpn).is_some() {
+                pte_ext::remap_huge(page_table, vpn, pte_flags);
+                vpn = VirtPageNum(vpn.0 + pte_ext::MEGAPAGE_PAGES);
+                continue;
+            }
+            if let Some(pte) = pte_ext::translate(page_table, vpn).filter(|pte| pte.is_valid()) {
+                let mut flags = pte_flags;
+                if !pte.writable() {
+                    flags.remove(PTEFlags::W);
+                }
+                pte_ext::remap(page_table, vpn, pte.ppn(), flags);
+            }
+            vpn.step();
+        }
+    }
+
+    pub fn with_class(mut self, class: AreaClass) -> Self {
+        self.class = Some(class);
+        self
+    }
+
+    /// class of the area, user if it is user-accessible unless set otherwise
+    pub fn class(&self) -> AreaClass {
+        self.class.unwrap_or(if self.allows_user() {
+            AreaClass::User
+        } else {
+            AreaClass::Kernel
+        })
+    }
+
+    pub fn with_stack(mut self) -> Self {
+        self.stack = true;
+        self
+    }
+
+    pub fn is_stack(&self) -> bool {
+        self.stack
+    }
+
+    /// Grow the area downwards on faults at most `max_gap` pages below its start, like
+    /// `MAP_GROWSDOWN`, as long as it stays within `max_pages` pages.
+    pub fn with_grows_down(mut self, max_gap: usize, max_pages: usize) -> Self {
+        self.grows_down = Some((max_gap, max_pages));
+        self
+    }
+
+    pub fn grows_down(&self) -> Option<(usize, usize)> {
+        self.grows_down
+    }
+
+    /// Keep the area, e.g. a persistent shared memory region or a vdso, when the memory set
+    /// is replaced by another program, see `MemorySet::replace_from_elf`.
+    pub fn with_keep_on_exec(mut self) -> Self {
+        self.keep_on_exec = true;
+        self
+    }
+
+    pub fn keeps_on_exec(&self) -> bool {
+        self.keep_on_exec
+    }
+
+    /// whether a fault on `vpn` below the area may grow it down to `vpn`
+    pub fn may_grow_down_to(&self, vpn: VirtPageNum) -> bool {
+        let start = self.vpn_range.get_start();
+        let end = self.vpn_range.get_end();
+        self.grows_down.is_some_and(|(max_gap, max_pages)| {
+            vpn < start && start.0 - vpn.0 <= max_gap && end.0 - vpn.0 <= max_pages
+        })
+    }
+
+    /// Map runs of `MEGAPAGE_PAGES` pages aligned to their size as sv39 megapages, the pages
+    /// around them as usual, e.g. for the direct map of a kernel or a large framed heap.
+    /// Identical areas always use megapages, framed areas without page colors only where the
+    /// frame allocator hands out an aligned run of contiguous frames, e.g. from memory never
+    /// allocated before. A megapage is split into pages again once part of it is unmapped
+    /// or changed.
+    pub fn with_huge_pages(mut self) -> Self {
+        self.huge_pages = true;
+        self
+    }
+
+    pub fn huge_pages(&self) -> bool {
+        self.huge_pages
+    }
+
+    /// Map pages of a lazy area read before they are written to a single zero frame shared
+    /// read-only by all such areas, the first write copies it like a copy-on-write page.
+    /// Saves frames for large, mostly untouched areas, e.g. bss-like allocations.
+    pub fn with_zero_page(mut self) -> Self {
+        self.zero_page = true;
+        self
+    }
+
+    pub fn zero_page(&self) -> bool {
+        self.zero_page
+    }
+
+    /// map `vpn` read-only to the zero frame, returns false if it cannot be allocated
+    pub(crate) fn map_zero_page(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+    ) -> bool {
+        debug_assert!(self.zero_page && self.map_type == MapType::Lazy);
+        let Some(frame) = zero_frame() else {
+            return false;
+        };
+        let ppn = frame.ppn;
+        // the frame stays shared with `ZERO_FRAME`, so a write always copies it
+        frames.insert(vpn, frame, PageState::ResidentCow);
+        self.map_page(page_table, vpn, ppn, self.initial_pte_flags() - PTEFlags::W);
+        true
+    }
+
+    /// start of the megapage covering `vpn` if it lies within the area and may be mapped as one
+    fn megapage_at(&self, vpn: VirtPageNum) -> Option<VirtPageNum> {
+        let start = VirtPageNum(vpn.0 - vpn.0 % pte_ext::MEGAPAGE_PAGES);
+        (self.huge_pages
+            && (self.map_type == MapType::Identical
+                || (self.map_type == MapType::Framed && self.page_colors.is_none()))
+            && self.vpn_range.get_start() <= start
+            && start.0 + pte_ext::MEGAPAGE_PAGES <= self.vpn_range.get_end().0)
+            .then_some(start)
+    }
+
+    /// physical page of the first page, for mmio areas
+    pub(crate) fn phys_start(&self) -> Option<PhysPageNum> {
+        self.phys_start
+    }
+
+    pub fn map_type(&self) -> MapType {
+        self.map_type
+    }
+
+    pub fn range(&self) -> VPNRange {
+        self.vpn_range
+    }
+
+    pub fn start_va(&self) -> VirtAddr {
+        self.vpn_range.get_start().into()
+    }
+
+    pub fn end_va(&self) -> VirtAddr {
+        self.vpn_range.get_end().into()
+    }
+
+    pub fn permission(&self) -> MapPermission {
+        self.map_perm
+    }
+
+    pub fn clone_strategy(&self) -> CloneStrategy {
+        self.clone_strategy
+    }
+
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+
+    pub fn is_resident(&self, frames: &FrameTable, vpn: VirtPageNum) -> bool {
+        frames.get(vpn).is_some_and(|info| info.frame().is_some())
+    }
+
+    /// whether the permission of the area allows the access
+    pub fn allows(&self, access: AccessType) -> bool {
+        match access {
+            AccessType::Read => self.map_perm.contains(MapPermission::R),
+            AccessType::Write => self.map_perm.contains(MapPermission::W),
+            AccessType::Execute => self.map_perm.contains(MapPermission::X),
+        }
+    }
+
+    /// whether the area is accessible from user mode
+    pub fn allows_user(&self) -> bool {
+        self.map_perm.contains(MapPermission::U)
+    }
+
+    /// number of virtual pages covered by the area
+    pub fn page_count(&self) -> usize {
+        self.vpn_range.get_end().0 - self.vpn_range.get_start().0
+    }
+
+    /// number of frames allocated for the area
+    pub fn resident_pages(&self, frames: &FrameTable) -> usize {
+        frames
+            .pages(self.vpn_range)
+            .filter(|(_, info)| info.frame().is_some())
+            .count()
+    }
+
+    pub fn map_one(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+    ) {
+        assert!(self.try_map_one(page_table, frames, vpn), "out of frames");
+    }
+
+    /// same as `map_one`, but returns false instead of panicking if no frame is left
+    pub(crate) fn try_map_one(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+    ) -> bool {
+        self.try_map_one_from(page_table, frames, vpn, None)
+    }
+
+    /// same as `try_map_one`, taking the frame from `pool` first unless the area is
+    /// page-colored
+    fn try_map_one_from(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+        pool: Option<&mut ZeroedPool>,
+    ) -> bool {
         let ppn: PhysPageNum = match self.map_type {
             MapType::Identical => PhysPageNum(vpn.0),
-            MapType::Framed => {
-                let frame = frame_alloc().unwrap();
+            MapType::Mmio => {
+                PhysPageNum(self.phys_start.unwrap().0 + vpn.0 - self.vpn_range.get_start().0)
+            }
+            MapType::Framed | MapType::Lazy | MapType::Shared => {
+                let pooled = match pool {
+                    Some(pool) if self.page_colors.is_none() => pool.take(),
+                    _ => None,
+                };
+                let Some(frame) = pooled.or_else(|| self.alloc_frame(vpn)) else {
+                    return false;
+                };
                 let ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                frames.insert(vpn, Arc::new(frame), PageState::Resident);
 
                 ppn
             }
         };
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits()).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        self.map_page(page_table, vpn, ppn, self.initial_pte_flags());
+        true
+    }
+
+    /// map a page with the software bits of the area
+    pub(crate) fn map_page(
+        &self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        ppn: PhysPageNum,
+        flags: PTEFlags,
+    ) {
+        page_table.map(vpn, ppn, flags);
+        if self.software_bits != 0 {
+            pte_ext::set_software_bits(page_table, vpn, self.software_bits);
+        }
+    }
+
+    fn alloc_frame(&mut self, vpn: VirtPageNum) -> Option<FrameTracker> {
+        let Some(colors) = self.page_colors else {
+            return frame_alloc();
+        };
+        let color = vpn.0 % colors;
+        if let Some(idx) = self
+            .spare_frames
+            .iter()
+            .position(|frame| frame.ppn.0 % colors == color)
+        {
+            return Some(self.spare_frames.swap_remove(idx));
+        }
+        for _ in 0..colors {
+            let Some(frame) = frame_alloc() else {
+                // out of frames, fall back to a spare one of another color
+                return self.spare_frames.pop();
+            };
+            if frame.ppn.0 % colors == color {
+                return Some(frame);
+            }
+            if self.spare_frames.len() >= colors {
+                // no frame of the color around, fall back to any frame
+                return Some(frame);
+            }
+            self.spare_frames.push(frame);
+        }
+        self.spare_frames.pop()
+    }
+
+    /// return the frames collected while searching for colors to the frame allocator
+    fn release_spare_frames(&mut self) {
+        self.spare_frames.clear();
+    }
+
+    fn pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.map_perm.bits()).unwrap()
+    }
+
+    /// flags of a newly mapped page, without W if writes have to be notified
+    pub(crate) fn initial_pte_flags(&self) -> PTEFlags {
+        let mut pte_flags = self.pte_flags();
+        if self.write_notify {
+            pte_flags.remove(PTEFlags::W);
+        }
+        pte_flags
+    }
+
+    /// map the frames of another area instead of allocating new ones,
+    /// write permission is dropped if `cow` is set so that the first write faults
+    pub fn share_frames(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        another: &Self,
+        another_frames: &FrameTable,
+        cow: bool,
+    ) {
+        let mut pte_flags = self.initial_pte_flags();
+        if cow {
+            pte_flags.remove(PTEFlags::W);
+        }
+        let state = if cow {
+            PageState::ResidentCow
+        } else {
+            PageState::Resident
+        };
+        for (vpn, info) in another_frames.pages(another.vpn_range) {
+            if let Some(frame) = info.frame() {
+                self.map_page(page_table, vpn, frame.ppn, pte_flags);
+                frames.insert(vpn, frame.clone(), state);
+            }
+        }
+    }
+
+    /// map the given frames, one per page in ascending order, instead of allocating new ones
+    pub fn map_frames(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        shared_frames: Vec<Arc<FrameTracker>>,
+    ) {
+        assert_eq!(shared_frames.len(), self.page_count());
+        self.map_frames_in(page_table, frames, self.vpn_range, shared_frames);
     }
 
-    pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+    fn map_frames_in(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn_range: VPNRange,
+        given_frames: Vec<Arc<FrameTracker>>,
+    ) {
+        let pte_flags = self.initial_pte_flags();
+        for (vpn, frame) in vpn_range.into_iter().zip(given_frames) {
+            self.map_page(page_table, vpn, frame.ppn, pte_flags);
+            frames.insert(vpn, frame, PageState::Resident);
         }
-        page_table.unmap(vpn);
     }
 
-    pub fn map(&mut self, page_table: &mut PageTable) {
+    /// mark the resident pages as shared copy-on-write, see `write_protect`
+    pub fn mark_cow(&self, frames: &mut FrameTable) {
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            if frames.state(vpn) == PageState::Resident {
+                frames.set_state(vpn, PageState::ResidentCow);
+            }
         }
     }
 
-    pub fn unmap(&mut self, page_table: &mut PageTable) {
+    /// drop write permission from the mapped pages so that the first write faults
+    pub fn write_protect(&mut self, page_table: &mut PageTable) {
+        let mut pte_flags = self.pte_flags();
+        pte_flags.remove(PTEFlags::W);
         for vpn in self.vpn_range {
-            self.unmap_one(page_table, vpn);
+            if let Some(pte) = pte_ext::translate(page_table, vpn).filter(|pte| pte.is_valid()) {
+                pte_ext::remap(page_table, vpn, pte.ppn(), pte_flags);
+            }
+        }
+    }
+
+    /// restore write permission of a write-protected page, false if copying a shared frame fails
+    pub fn resolve_write(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+    ) -> bool {
+        let copy = if self.needs_copy(frames, vpn) {
+            let frame = self.alloc_frame(vpn);
+            self.release_spare_frames();
+            match frame {
+                Some(frame) => Some(Arc::new(frame)),
+                None => return false,
+            }
+        } else {
+            None
+        };
+        self.restore_write(page_table, frames, vpn, copy);
+        true
+    }
+
+    /// Give the page a copy of its frame if the frame is mapped elsewhere as well, e.g. by a
+    /// copy-on-write clone, a shared segment or a mirror, so that writing it stays private.
+    /// False if frames run out.
+    pub(crate) fn unshare(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+    ) -> bool {
+        if !frames.get(vpn).is_some_and(|info| info.is_shared()) {
+            return true;
         }
+        let frame = self.alloc_frame(vpn);
+        self.release_spare_frames();
+        let Some(frame) = frame else {
+            return false;
+        };
+        self.restore_write(page_table, frames, vpn, Some(Arc::new(frame)));
+        true
     }
 
-    pub fn shrink_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+    /// whether the page is copy-on-write and its frame is still shared with another area
+    pub(crate) fn needs_copy(&self, frames: &FrameTable, vpn: VirtPageNum) -> bool {
+        frames
+            .get(vpn)
+            .is_some_and(|info| info.state() == PageState::ResidentCow && info.is_shared())
+    }
+
+    /// restore write permission of a write-protected page, the shared frame of the page
+    /// is replaced by `copy` if given, this neither allocates frames nor heap memory
+    pub(crate) fn restore_write(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+        copy: Option<Arc<FrameTracker>>,
+    ) {
+        let ppn = match copy {
+            Some(copy) => {
+                let ppn = copy.ppn;
+                let frame = frames
+                    .replace_frame(vpn, copy, PageState::Resident)
+                    .unwrap();
+                ppn.get_bytes_array()
+                    .copy_from_slice(frame.ppn.get_bytes_array());
+                ppn
+            }
+            None => {
+                // the last sharer of a copy-on-write frame owns it
+                if frames.state(vpn) == PageState::ResidentCow {
+                    frames.set_state(vpn, PageState::Resident);
+                }
+                pte_ext::translate(page_table, vpn).unwrap().ppn()
+            }
+        };
+        pte_ext::remap(page_table, vpn, ppn, self.pte_flags());
+    }
+
+    /// take over a frame mapped at `vpn` outside of the area
+    pub(crate) fn adopt_frame(
+        &mut self,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+        frame: Arc<FrameTracker>,
+    ) {
+        frames.insert(vpn, frame, PageState::Resident);
+    }
+
+    /// map a page of a lazy area holding `data`, the rest of the page is zeroed,
+    /// returns false if frames ran out
+    pub(crate) fn fill_page(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+        data: &[u8],
+    ) -> bool {
+        assert_eq!(self.map_type, MapType::Lazy);
+        assert!(data.len() <= PAGE_SIZE);
+
+        if !self.try_map_one(page_table, frames, vpn) {
+            return false;
+        }
+        self.release_spare_frames();
+        let ppn = pte_ext::translate(page_table, vpn).unwrap().ppn();
+        ppn.get_bytes_array()[..data.len()].copy_from_slice(data);
+        true
+    }
+
+    pub fn unmap_one(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+    ) {
+        if frames.state(vpn) == PageState::SwappedOut {
+            // nothing mapped, dropping the page frees its swap slot
+            frames.remove(vpn);
+            pte_ext::clear_swapped(page_table, vpn);
+            return;
+        }
+        match self.map_type {
+            MapType::Identical | MapType::Mmio => {}
+            MapType::Framed | MapType::Shared => {
+                frames.remove(vpn);
+            }
+            MapType::Lazy => {
+                frames.remove(vpn);
+                if !pte_ext::translate(page_table, vpn).is_some_and(|pte| pte.is_valid()) {
+                    // never accessed, nothing mapped
+                    return;
+                }
+            }
+        }
+        pte_ext::unmap(page_table, vpn);
+    }
+
+    pub fn map(&mut self, page_table: &mut PageTable, frames: &mut FrameTable) {
+        if self.map_type == MapType::Lazy {
+            return;
+        }
+        let mut vpn = self.vpn_range.get_start();
+        while vpn < self.vpn_range.get_end() {
+            if self.megapage_at(vpn) == Some(vpn) && self.map_megapage(page_table, frames, vpn) {
+                vpn = VirtPageNum(vpn.0 + pte_ext::MEGAPAGE_PAGES);
+                continue;
+            }
+            self.map_one(page_table, frames, vpn);
+            vpn.step();
+        }
+        self.release_spare_frames();
+    }
+
+    /// map the megapage starting at `vpn`, returns false to fall back to pages
+    fn map_megapage(
+        &self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+    ) -> bool {
+        let run = match self.map_type {
+            MapType::Identical => Vec::new(),
+            _ => match alloc_megapage_frames() {
+                Some(run) => run,
+                None => return false,
+            },
+        };
+        let ppn = run.first().map_or(PhysPageNum(vpn.0), |frame| frame.ppn);
+        if !pte_ext::map_huge(
+            page_table,
+            frames.tables_mut(),
+            vpn,
+            ppn,
+            self.initial_pte_flags(),
+            self.software_bits,
+        ) {
+            return false;
+        }
+        for (idx, frame) in run.into_iter().enumerate() {
+            frames.insert(
+                VirtPageNum(vpn.0 + idx),
+                Arc::new(frame),
+                PageState::Resident,
+            );
+        }
+        true
+    }
+
+    /// Map the faulting page `vpn` of a lazy area, pages following it are mapped
+    /// as well if the area is accessed sequentially. Returns the range of pages
+    /// around the fault that are resident now, `None` if no frame is left for `vpn`.
+    pub(crate) fn fault_in(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        vpn: VirtPageNum,
+        fault_around: &mut FaultAround,
+        mut pool: Option<&mut ZeroedPool>,
+    ) -> Option<VPNRange> {
+        assert_eq!(self.map_type, MapType::Lazy);
+
+        if !self.try_map_one_from(page_table, frames, vpn, pool.as_deref_mut()) {
+            self.release_spare_frames();
+            return None;
+        }
+        let mut end = VirtPageNum(vpn.0 + 1);
+        if self.access_hint == AccessHint::Sequential {
+            let window_end =
+                VirtPageNum((vpn.0 + 1 + fault_around.window()).min(self.vpn_range.get_end().0));
+            for next in VPNRange::new(VirtPageNum(vpn.0 + 1), window_end) {
+                // prefetching is best effort, stop once frames run out
+                if !frames.contains(next) {
+                    if !self.try_map_one_from(page_table, frames, next, pool.as_deref_mut()) {
+                        break;
+                    }
+                    fault_around.record(next);
+                }
+                end = VirtPageNum(next.0 + 1);
+            }
+        }
+        self.release_spare_frames();
+
+        Some(VPNRange::new(vpn, end))
+    }
+
+    pub fn unmap(&mut self, page_table: &mut PageTable, frames: &mut FrameTable) {
+        let mut vpn = self.vpn_range.get_start();
+        while vpn < self.vpn_range.get_end() {
+            if self.megapage_at(vpn) == Some(vpn) && pte_ext::huge_leaf(page_table, vpn).is_some() {
+                pte_ext::unmap_huge(page_table, vpn);
+                let end = VirtPageNum(vpn.0 + pte_ext::MEGAPAGE_PAGES);
+                for page in VPNRange::new(vpn, end) {
+                    frames.remove(page);
+                }
+                vpn = end;
+                continue;
+            }
+            self.unmap_one(page_table, frames, vpn);
+            vpn.step();
+        }
+    }
+
+    pub fn shrink_to(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        new_end: VirtPageNum,
+    ) {
         for vpn in VPNRange::new(new_end, self.vpn_range.get_end()) {
-            self.unmap_one(page_table, vpn)
+            self.unmap_one(page_table, frames, vpn)
         }
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
     }
 
-    pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
-        for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
-            self.map_one(page_table, vpn)
+    pub fn append_to(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        new_end: VirtPageNum,
+    ) {
+        if self.map_type != MapType::Lazy {
+            for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
+                self.map_one(page_table, frames, vpn)
+            }
+        }
+        self.release_spare_frames();
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
+
+    /// move the start of the area down to `new_start`, mapping the new pages unless lazy
+    pub(crate) fn prepend_to(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        new_start: VirtPageNum,
+    ) {
+        if self.map_type != MapType::Lazy {
+            for vpn in VPNRange::new(new_start, self.vpn_range.get_start()) {
+                self.map_one(page_table, frames, vpn)
+            }
+        }
+        self.release_spare_frames();
+        self.vpn_range = VPNRange::new(new_start, self.vpn_range.get_end());
+    }
+
+    /// Move the area to start at `new_start`, its frames and ptes move along without copying
+    /// the pages. The new range must not overlap any mapping.
+    pub(crate) fn move_to(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        new_start: VirtPageNum,
+    ) {
+        assert!(!self.map_type.is_direct());
+        let offset =
+            |vpn: VirtPageNum| VirtPageNum(vpn.0 - self.vpn_range.get_start().0 + new_start.0);
+        let resident = frames
+            .pages(self.vpn_range)
+            .map(|(vpn, _)| vpn)
+            .collect::<Vec<_>>();
+        for vpn in resident {
+            if let Some(pte) = pte_ext::translate(page_table, vpn).filter(|pte| pte.is_valid()) {
+                let bits = pte_ext::software_bits(&pte);
+                pte_ext::unmap(page_table, vpn);
+                page_table.map(offset(vpn), pte.ppn(), pte.flags());
+                if bits != 0 {
+                    pte_ext::set_software_bits(page_table, offset(vpn), bits);
+                }
+            } else if pte_ext::clear_swapped(page_table, vpn) {
+                pte_ext::mark_swapped(page_table, offset(vpn));
+            }
+            frames.rekey(vpn, offset(vpn));
+        }
+        self.vpn_range = VPNRange::new(new_start, offset(self.vpn_range.get_end()));
+    }
+
+    /// same as `append_to`, but new pages of a framed area are backed by the given frames
+    pub(crate) fn append_with_frames(
+        &mut self,
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        new_end: VirtPageNum,
+        given_frames: Vec<Arc<FrameTracker>>,
+    ) {
+        let appended = VPNRange::new(self.vpn_range.get_end(), new_end);
+        match self.map_type {
+            MapType::Framed | MapType::Shared => {
+                self.map_frames_in(page_table, frames, appended, given_frames)
+            }
+            MapType::Identical | MapType::Mmio => {
+                for vpn in appended {
+                    self.map_one(page_table, frames, vpn)
+                }
+            }
+            MapType::Lazy => {}
         }
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
     }
 
+    /// content of the area, pages not mapped read as zeros
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn read_to_vec(&self, page_table: &PageTable) -> Vec<u8> {
+        let mut content = Vec::with_capacity(self.page_count() * PAGE_SIZE);
+        for vpn in self.vpn_range {
+            match pte_ext::translate(page_table, vpn).filter(|pte| pte.is_valid()) {
+                Some(pte) => content.extend_from_slice(pte.ppn().get_bytes_array()),
+                None => content.resize(content.len() + PAGE_SIZE, 0),
+            }
+        }
+        content
+    }
+
     /// data: start-aligned but maybe with shorter length
     /// assume that all frames were cleared before
     pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
-        assert_eq!(self.map_type, MapType::Framed);
+        assert!(self.map_type.is_framed());
 
         let mut start: usize = 0;
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
         loop {
             let src = &data[start..len.min(start + PAGE_SIZE)];
-            let dst = &mut page_table
-                .translate(current_vpn)
+            let dst = &mut pte_ext::translate(page_table, current_vpn)
                 .unwrap()
                 .ppn()
                 .get_bytes_array()[..src.len()];
@@ -1,19 +1,103 @@
-use alloc::vec::Vec;
+use alloc::{
+    boxed::Box,
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    cmp::Ordering,
+    fmt,
+    sync::atomic::{self, AtomicU64},
+};
 use page_table::{
-    PTEFlags, PageTable, PageTableEntry, PhysAddr, PhysPageNum, VirtAddr, VirtPageNum, PAGE_SIZE,
+    frame_alloc, FrameTracker, PTEFlags, PageTable, PageTableEntry, PhysAddr, PhysPageNum,
+    VPNRange, VirtAddr, VirtPageNum, PAGE_SIZE,
 };
-use xmas_elf::program::Flags;
+use spin::Mutex;
 
-use crate::MemorySetBuilder;
+use crate::{
+    canary, checkpoint, clock::Clock, elf, elf::LoadPlan, fault_around::FaultAround, hw,
+    page_table_usage::PageTableUsage, pte_ext, registry, swap_backend::SwapSlot,
+    transaction::StagedOp, AccessType, ActivateError, AddressExplanation, AllocPolicy, AreaClass,
+    AreaHandle, AreaOrigin, AreaSummary, Backing, BinFmt, Canary, CheckpointError, CloneStrategy,
+    DelegatedRegion, ElfLoadError, ElfLoadPolicy, FaultResult, FaultScratch, FixedMapping,
+    FixedMode, FrameTable, LoadResult, MemoryStats, OvercommitPolicy, PageAccessError, PageInfo,
+    PageState, Phase, PhysicalOverlap, ProgramHeaders, ProgramLayout, SwapBackend, Teardown,
+    TempAreaGuard, TextSegmentKey, TextSegmentRegistry, Transaction, TransactionError, Translation,
+    UserfaultResolution, VaBuddy, Violation, VmPolicy, Window, ZeroedPool,
+    DEFAULT_EAGER_COPY_PAGES, MAX_ASID_BITS, WIRE_VERSION,
+};
 
 use super::{map_type::MapType, memory_area::MapArea, MapPermission};
-use core::arch::asm;
-use riscv::register::satp;
+
+/// pages of the lower half of sv39, where user areas live
+const USER_SPACE_PAGES: usize = 1 << 26;
+/// generation of the next memory set created
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+/// pages below 4 GiB
+const LOW32_PAGES: usize = 1 << 20;
+/// position of the asid field in satp
+const SATP_ASID_SHIFT: usize = 44;
 
 /// memory set structure, controls virtual-memory space
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    /// frames backing the pages of the areas
+    frames: FrameTable,
+    page_table_usage: PageTableUsage,
+    write_notifier: Option<Box<dyn FnMut(VirtPageNum) + Send>>,
+    /// frees frames when a fault runs out of them, see `set_reclaim_callback`
+    reclaim_callback: Option<Box<dyn FnMut() -> bool + Send>>,
+    /// pages written since soft-dirty tracking was enabled or last cleared
+    soft_dirty: Option<BTreeSet<VirtPageNum>>,
+    fault_around: FaultAround,
+    /// pages mapped by `handle_page_fault_nonalloc`, not yet handed to their areas
+    pending_frames: Vec<(VirtPageNum, Arc<FrameTracker>)>,
+    /// pages written in `handle_page_fault_nonalloc`, not yet recorded as soft-dirty
+    pending_dirty: Vec<VirtPageNum>,
+    /// ranges whose missing pages are populated by the registrant, with its token
+    userfault_regions: Vec<(VPNRange, usize)>,
+    trampoline: Option<VirtPageNum>,
+    /// further kernel pages pinned like the trampoline, see `map_fixed`
+    fixed_mappings: Vec<FixedMapping>,
+    /// physical ranges intentionally mapped both framed and identically
+    physical_aliases: Vec<(PhysPageNum, PhysPageNum)>,
+    /// copy-on-write frames copied on a write
+    cow_breaks: usize,
+    /// copy-on-write areas of at most this many pages are copied eagerly on clone
+    eager_copy_pages: usize,
+    /// ranges handed to child managers, by id
+    delegations: BTreeMap<usize, VPNRange>,
+    next_delegation: usize,
+    overcommit: OvercommitPolicy,
+    /// pages that may be committed, see `OvercommitPolicy`
+    commit_limit: usize,
+    /// start of the heap and the current break
+    heap: Option<(VirtAddr, VirtAddr)>,
+    /// areas sharing their frames with an area of another memory set, and that area
+    mirrors: Vec<(AreaHandle, AreaHandle)>,
+    zeroed_pool: Option<ZeroedPool>,
+    /// program headers of the loaded elf
+    program_headers: Option<ProgramHeaders>,
+    canary: Option<Canary>,
+    /// address space id folded into the token, 0 for none
+    asid: usize,
+    /// distinguishes memory sets reusing a root page table frame, see `activate_checked`
+    generation: u64,
+    /// flush generation of the page table each hart last activated it at, see `activate`
+    activated_at: Mutex<BTreeMap<usize, u64>>,
+    /// where `evict` writes pages to
+    swap_backend: Option<Arc<Mutex<dyn SwapBackend>>>,
+    /// hand and order of `reclaim`
+    clock: Clock,
+    /// fast path for small mmaps, see `set_mmap_buddy`
+    mmap_buddy: Option<VaBuddy>,
+    /// reject writable and executable mappings, see `set_forbid_wx`
+    forbid_wx: bool,
+    #[cfg(feature = "symbols")]
+    symbols: Option<crate::elf::SymbolIndex>,
 }
 
 impl MemorySet {
@@ -21,11 +105,61 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            frames: FrameTable::new(),
+            page_table_usage: PageTableUsage::new(),
+            write_notifier: None,
+            reclaim_callback: None,
+            soft_dirty: None,
+            fault_around: FaultAround::new(),
+            pending_frames: Vec::new(),
+            pending_dirty: Vec::new(),
+            userfault_regions: Vec::new(),
+            trampoline: None,
+            fixed_mappings: Vec::new(),
+            physical_aliases: Vec::new(),
+            cow_breaks: 0,
+            eager_copy_pages: DEFAULT_EAGER_COPY_PAGES,
+            delegations: BTreeMap::new(),
+            next_delegation: 0,
+            overcommit: OvercommitPolicy::default(),
+            commit_limit: usize::MAX,
+            heap: None,
+            mirrors: Vec::new(),
+            zeroed_pool: None,
+            program_headers: None,
+            canary: None,
+            asid: 0,
+            generation: NEXT_GENERATION.fetch_add(1, atomic::Ordering::Relaxed),
+            activated_at: Mutex::new(BTreeMap::new()),
+            swap_backend: None,
+            clock: Clock::new(),
+            mmap_buddy: None,
+            forbid_wx: false,
+            #[cfg(feature = "symbols")]
+            symbols: None,
         }
     }
 
+    /// satp value of the memory set, its asid included
     pub fn token(&self) -> usize {
-        self.page_table.token()
+        self.page_table.token() | self.asid << SATP_ASID_SHIFT
+    }
+
+    /// address space id, 0 if none was set
+    pub fn asid(&self) -> usize {
+        self.asid
+    }
+
+    /// tag the tlb entries of the memory set with `asid`, see `AsidAllocator`
+    pub fn set_asid(&mut self, asid: usize) {
+        assert!(asid < 1 << MAX_ASID_BITS, "asid out of range");
+        self.asid = asid;
+        self.activated_at.lock().clear();
+    }
+
+    /// areas of the memory set in no particular order, e.g. for diagnostics or accounting
+    pub fn areas(&self) -> impl Iterator<Item = &MapArea> {
+        self.areas.iter()
     }
 
     /// Assume that no conflicts.
@@ -41,172 +175,3462 @@ impl MemorySet {
         );
     }
 
+    /// Assume that no conflicts. No frame is allocated until a page is accessed,
+    /// see `handle_page_fault`.
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        self.push(
+            MapArea::new(start_va, end_va, MapType::Lazy, permission),
+            None,
+        );
+    }
+
     pub fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
-        map_area.map(&mut self.page_table);
+        Self::check_class(&map_area);
+        self.check_wx(&map_area);
+        map_area.map(&mut self.page_table, &mut self.frames);
+        self.page_table_usage
+            .record_mapped(&self.page_table, map_area.vpn_range);
+        if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+            soft_dirty.extend(map_area.vpn_range);
+        }
         if let Some(data) = data {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        self.debug_assert_invariants();
     }
 
-    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
-        if let Some((idx, area)) = self
-            .areas
-            .iter_mut()
-            .enumerate()
-            .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
-        {
-            area.unmap(&mut self.page_table);
-            self.areas.remove(idx);
+    /// `push`, but fails with `TransactionError::Overlap` instead of double-mapping when the
+    /// area intersects another area, the trampoline or a fixed mapping, and with
+    /// `UserKernelArea` or `WritableExecutable` instead of panicking.
+    pub fn push_checked(
+        &mut self,
+        map_area: MapArea,
+        data: Option<&[u8]>,
+    ) -> Result<(), TransactionError> {
+        let (start, end) = (map_area.vpn_range.get_start(), map_area.vpn_range.get_end());
+        if self.overlaps_any(start, end) || self.fixed_in(start, end) {
+            return Err(TransactionError::Overlap);
         }
+        self.validate_push(&map_area)?;
+        self.push(map_area, data);
+        Ok(())
     }
 
-    pub fn recycle_data_pages(&mut self) {
-        self.areas.clear();
+    /// Map a copy of `data`, e.g. the device tree or boot arguments of init, page-aligned in
+    /// a free user region with `perm` and U, and return its address. The blob is placed above
+    /// all user areas, one guard page apart. `None` if there is no room for it, `perm` is
+    /// refused or frames run out.
+    pub fn map_boot_blob(&mut self, data: &[u8], perm: MapPermission) -> Option<VirtAddr> {
+        let pages = data.len().div_ceil(PAGE_SIZE).max(1);
+        let start = self.find_free_range(pages, 1, Window::Default)?;
+        let start_va: VirtAddr = start.into();
+        let end_va: VirtAddr = VirtPageNum(start.0 + pages).into();
+        let area = MapArea::new(start_va, end_va, MapType::Framed, perm | MapPermission::U)
+            .with_origin(AreaOrigin::KernelInternal);
+        self.validate_push(&area).ok()?;
+        self.try_push(area, Some(data), AllocPolicy::Block).ok()?;
+        Some(start_va)
+    }
+
+    /// Map an anonymous area of `len` bytes in a free region of `window`, populated on
+    /// demand, and return its address. `None` if the window has no room for it.
+    pub fn mmap_in(&mut self, len: usize, perm: MapPermission, window: Window) -> Option<VirtAddr> {
+        self.mmap_aligned(len, PAGE_SIZE, perm, window)
+    }
+
+    /// Like `mmap_in`, the area starts at a multiple of `align`, a power of two of at least
+    /// a page, e.g. 2 MiB for an allocator arena. The placement search skips to aligned
+    /// starts, so nothing is reserved beyond `len`.
+    pub fn mmap_aligned(
+        &mut self,
+        len: usize,
+        align: usize,
+        perm: MapPermission,
+        window: Window,
+    ) -> Option<VirtAddr> {
+        if len == 0 || !align.is_power_of_two() || align < PAGE_SIZE {
+            return None;
+        }
+        let pages = len.div_ceil(PAGE_SIZE);
+        if !self.may_commit(pages, perm) || self.is_wx_forbidden(perm) {
+            return None;
+        }
+        let start_va: VirtAddr = match self.buddy_alloc(pages, align / PAGE_SIZE, window) {
+            Some(start) => start.into(),
+            None => self
+                .find_free_range(pages, align / PAGE_SIZE, window)?
+                .into(),
+        };
+        self.push(
+            MapArea::new(start_va, VirtAddr(start_va.0 + len), MapType::Lazy, perm)
+                .with_origin(AreaOrigin::Mmap),
+            None,
+        );
+        Some(start_va)
+    }
+
+    /// Place mmaps in `Window::Default` of at most `1 << buddy.max_order()` pages, rounded up
+    /// to a power of two, in blocks of `buddy` instead of searching the gaps between areas.
+    /// Larger mmaps and mmaps in other windows take the general search, which skips the
+    /// window of the buddy. Blocks are taken back once no area overlaps them anymore.
+    pub fn set_mmap_buddy(&mut self, buddy: Option<VaBuddy>) {
+        self.mmap_buddy = buddy;
     }
 
-    pub fn activate(&self) {
-        let satp = self.page_table.token();
-        unsafe {
-            satp::write(satp);
-            asm!("sfence.vma");
+    pub fn mmap_buddy(&self) -> Option<&VaBuddy> {
+        self.mmap_buddy.as_ref()
+    }
+
+    /// a block of the mmap buddy free for `pages` pages aligned to `align` pages, if any
+    fn buddy_alloc(&mut self, pages: usize, align: usize, window: Window) -> Option<VirtPageNum> {
+        let order = pages.max(align).next_power_of_two().trailing_zeros() as usize;
+        if !matches!(window, Window::Default)
+            || !self
+                .mmap_buddy
+                .as_ref()
+                .is_some_and(|buddy| order <= buddy.max_order())
+        {
+            return None;
+        }
+        for sweep in [false, true] {
+            if sweep {
+                self.sweep_mmap_buddy();
+            }
+            while let Some(start) = self.mmap_buddy.as_mut().unwrap().alloc(order) {
+                // blocks taken by other mappings stay handed out until the sweep
+                let end = VirtPageNum(start.0 + pages);
+                if !self.overlaps_any(start, end) && !self.fixed_in(start, end) {
+                    return Some(start);
+                }
+            }
         }
+        None
     }
 
-    pub fn map_trampoline(&mut self, vpn: VirtPageNum, ppn: PhysPageNum) {
-        self.page_table.map(vpn, ppn, PTEFlags::R | PTEFlags::X);
+    /// take back the blocks of the mmap buddy no mapping overlaps anymore
+    fn sweep_mmap_buddy(&mut self) {
+        let Some(buddy) = self.mmap_buddy.as_ref() else {
+            return;
+        };
+        let unused = buddy
+            .allocated()
+            .filter(|range| {
+                !self.overlaps_any(range.get_start(), range.get_end())
+                    && !self.fixed_in(range.get_start(), range.get_end())
+            })
+            .map(|range| range.get_start())
+            .collect::<Vec<_>>();
+        let buddy = self.mmap_buddy.as_mut().unwrap();
+        for vpn in unused {
+            buddy.free(vpn);
+        }
     }
 
-    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.page_table.translate(vpn)
+    /// Map an anonymous area of `len` bytes in a free region above all user areas,
+    /// populated on demand, and return its address.
+    pub fn mmap_anonymous(&mut self, len: usize, perm: MapPermission) -> Option<VirtAddr> {
+        self.mmap_in(len, perm, Window::Default)
     }
 
-    pub fn shrink_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
-        if let Some(area) = self
+    /// Find an unused range of `len` bytes starting at a multiple of `align`, a power of two of
+    /// at least a page. `hint` is taken if the range there is free and aligned, otherwise the
+    /// range is placed above all user areas.
+    pub fn find_free_area(
+        &self,
+        len: usize,
+        align: usize,
+        hint: Option<VirtAddr>,
+    ) -> Option<VirtAddr> {
+        if len == 0 || !align.is_power_of_two() || align < PAGE_SIZE {
+            return None;
+        }
+        let pages = len.div_ceil(PAGE_SIZE);
+        let align_pages = align / PAGE_SIZE;
+        if let Some(hint) = hint.filter(|hint| hint.0 & (align - 1) == 0) {
+            let start = hint.floor();
+            let end = VirtPageNum(start.0.saturating_add(pages));
+            if start.0 > 0
+                && end.0 <= USER_SPACE_PAGES
+                && !self.overlaps_any(start, end)
+                && !self.fixed_in(start, end)
+            {
+                return Some(hint);
+            }
+        }
+        self.find_free_range(pages, align_pages, Window::Default)
+            .map(Into::into)
+    }
+
+    /// first `pages` free pages in `window` starting at a multiple of `align` pages,
+    /// page 0 is never handed out
+    fn find_free_range(&self, pages: usize, align: usize, window: Window) -> Option<VirtPageNum> {
+        let align_up = |page: usize| (page + align - 1) & !(align - 1);
+        let (mut start, limit) = match window {
+            Window::Low32 => (1, LOW32_PAGES),
+            Window::Default => (
+                self.areas
+                    .iter()
+                    .filter(|area| area.allows_user())
+                    .map(|area| area.vpn_range.get_end().0 + 1)
+                    .max()
+                    .unwrap_or(1),
+                USER_SPACE_PAGES,
+            ),
+            Window::Custom(range) => (
+                range.get_start().0.max(1),
+                range.get_end().0.min(USER_SPACE_PAGES),
+            ),
+        };
+        let mut taken = self
             .areas
-            .iter_mut()
-            .find(|area| area.vpn_range.get_start() == start.floor())
+            .iter()
+            .map(|area| (area.vpn_range.get_start().0, area.vpn_range.get_end().0))
+            .chain(self.fixed_pages().map(|vpn| (vpn.0, vpn.0 + 1)))
+            .chain(self.mmap_buddy.as_ref().map(|buddy| {
+                let range = buddy.range();
+                (range.get_start().0, range.get_end().0)
+            }))
+            .collect::<Vec<_>>();
+        taken.sort();
+        start = align_up(start);
+        for (taken_start, taken_end) in taken {
+            if start + pages <= taken_start {
+                break;
+            }
+            if start < taken_end {
+                start = align_up(taken_end);
+            }
+        }
+        (start + pages <= limit).then_some(VirtPageNum(start))
+    }
+
+    /// Map an anonymous area of `len` bytes at exactly `start`, populated on demand. Returns
+    /// false if `start` is not page-aligned, if the range leaves user space, covers the
+    /// trampoline or a fixed mapping or overlaps a kernel area, if it overlaps any area in
+    /// `FixedMode::FailOnOverlap`, or if the pages cannot be committed, pages about to be
+    /// replaced still count then. Other harts must flush their tlb if areas were replaced.
+    pub fn mmap_fixed(
+        &mut self,
+        start: VirtAddr,
+        len: usize,
+        perm: MapPermission,
+        mode: FixedMode,
+    ) -> bool {
+        let Some(end) = start.0.checked_add(len).map(VirtAddr) else {
+            return false;
+        };
+        if !start.aligned() || len == 0 || !self.is_user_range(start.floor(), end.ceil()) {
+            return false;
+        }
+        if !self.may_commit(len.div_ceil(PAGE_SIZE), perm) || self.is_wx_forbidden(perm) {
+            return false;
+        }
+        match mode {
+            FixedMode::ReplaceExisting => self.unmap_pages(start.floor(), end.ceil()),
+            FixedMode::FailOnOverlap if self.overlaps_any(start.floor(), end.ceil()) => {
+                return false
+            }
+            FixedMode::FailOnOverlap => {}
+        }
+        self.push(
+            MapArea::new(start, end, MapType::Lazy, perm).with_origin(AreaOrigin::Mmap),
+            None,
+        );
+        true
+    }
+
+    /// Unmap the pages of `start_va..end_va`, like `munmap`. Areas sticking out of the range
+    /// keep the rest, an area holding the whole range is split in two around the hole.
+    /// Returns false if `start_va` is not page-aligned, the range is empty, leaves user space
+    /// or touches a kernel area or the fixed mappings. Other harts must flush their tlb
+    /// afterwards.
+    pub fn unmap_range(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        if !start_va.aligned()
+            || end_va <= start_va
+            || !self.is_user_range(start_va.floor(), end_va.ceil())
         {
-            area.shrink_to(&mut self.page_table, new_end.ceil());
-            true
-        } else {
-            false
+            return false;
         }
+        self.unmap_pages(start_va.floor(), end_va.ceil());
+        true
     }
 
-    pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
-        if let Some(area) = self
+    /// Move the pages of `start_va..end_va` to `target` at `target_va` without copying them,
+    /// like an l4 grant: they are unmapped here and a framed area of the same permission maps
+    /// their frames there. The range must lie in one user area, each page must be backed by
+    /// a private resident frame and the target range must lie in user space, otherwise
+    /// `InvalidRange` is returned; `NotFound` if no area covers the range, `Overlap` if the
+    /// target range is taken, `OutOfMemory` if the pages exceed the commit limit of `target`
+    /// and `WritableExecutable` if `target` forbids the permission. Other harts must flush
+    /// their tlb.
+    pub fn donate_pages(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        target: &mut MemorySet,
+        target_va: VirtAddr,
+    ) -> Result<(), TransactionError> {
+        if !start_va.aligned() || !target_va.aligned() || end_va <= start_va {
+            return Err(TransactionError::InvalidRange);
+        }
+        let (start, end) = (start_va.floor(), end_va.ceil());
+        self.settle_faults();
+        target.settle_faults();
+        let area = self
             .areas
-            .iter_mut()
-            .find(|area| area.vpn_range.get_start() == start.floor())
+            .iter()
+            .find(|area| area.contains(start))
+            .ok_or(TransactionError::NotFound)?;
+        if end > area.vpn_range.get_end()
+            || !matches!(area.map_type(), MapType::Framed | MapType::Lazy)
+            || area.class() != AreaClass::User
         {
-            area.append_to(&mut self.page_table, new_end.ceil());
-            true
-        } else {
-            false
+            return Err(TransactionError::InvalidRange);
+        }
+        let target_start = target_va.floor();
+        let target_end = VirtPageNum(target_start.0 + end.0 - start.0);
+        if target_end.0 > USER_SPACE_PAGES {
+            return Err(TransactionError::InvalidRange);
+        }
+        if target.overlaps_any(target_start, target_end)
+            || target.fixed_in(target_start, target_end)
+        {
+            return Err(TransactionError::Overlap);
+        }
+        if !target.may_commit(end.0 - start.0, area.permission()) {
+            return Err(TransactionError::OutOfMemory);
         }
+        let donated = VPNRange::new(start, end)
+            .into_iter()
+            .map(|vpn| {
+                self.frames
+                    .get(vpn)
+                    .filter(|info| info.state() == PageState::Resident && !info.is_shared())
+                    .and_then(|info| info.frame().cloned())
+                    .ok_or(TransactionError::InvalidRange)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let donee = MapArea::new(
+            target_start.into(),
+            target_end.into(),
+            MapType::Framed,
+            area.permission(),
+        );
+        target.validate_push(&donee)?;
+
+        // the frames survive the unmap through `donated`
+        self.unmap_pages(start, end);
+        target.push_with_frames(donee, donated);
+        Ok(())
     }
 
-    /// clone the memory set
-    pub fn from_existed_user(
-        user_space: &Self,
-        trampline_start_va: usize,
-        trampline_start_pa: usize,
-    ) -> Self {
-        let mut memory_set = Self::new_bare();
+    pub fn overcommit_policy(&self) -> OvercommitPolicy {
+        self.overcommit
+    }
 
-        memory_set.map_trampoline(
-            VirtAddr::from(trampline_start_va).into(),
-            PhysAddr::from(trampline_start_pa as usize).into(),
+    pub fn set_overcommit_policy(&mut self, policy: OvercommitPolicy) {
+        self.overcommit = policy;
+    }
+
+    pub fn commit_limit(&self) -> usize {
+        self.commit_limit
+    }
+
+    /// Set the pages that may be committed, consulted by mmap according to the overcommit
+    /// policy. Areas already mapped are kept even if they exceed it.
+    pub fn set_commit_limit(&mut self, pages: usize) {
+        self.commit_limit = pages;
+    }
+
+    /// pages promised to private writable areas, whether backed by frames yet or not
+    pub fn committed_pages(&self) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| !area.map_type().is_direct() && area.allows(AccessType::Write))
+            .map(|area| area.page_count())
+            .sum()
+    }
+
+    /// whether `pages` more pages with `perm` may be promised, only writable memory needs
+    /// frames of its own
+    fn may_commit(&self, pages: usize, perm: MapPermission) -> bool {
+        if !perm.contains(MapPermission::W) {
+            return true;
+        }
+        match self.overcommit {
+            OvercommitPolicy::Always => true,
+            OvercommitPolicy::Heuristic => pages <= self.commit_limit,
+            OvercommitPolicy::Never => self
+                .committed_pages()
+                .checked_add(pages)
+                .is_some_and(|total| total <= self.commit_limit),
+        }
+    }
+
+    /// Designate an empty heap at `start`, grown and shrunk by `set_brk` and `sbrk`. Returns
+    /// false if there is a heap already, `start` is not page-aligned or lies in an area.
+    pub fn create_heap(&mut self, start: VirtAddr) -> bool {
+        if self.heap.is_some()
+            || !start.aligned()
+            || self.overlaps_any(start.floor(), VirtPageNum(start.floor().0 + 1))
+        {
+            return false;
+        }
+        self.push(
+            MapArea::new(
+                start,
+                start,
+                MapType::Lazy,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            )
+            .with_origin(AreaOrigin::Brk),
+            None,
         );
+        self.heap = Some((start, start));
+        true
+    }
 
-        // copy data sections/trap_context/user_stack
-        for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
-            // copy data from another space
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn
-                    .get_bytes_array()
-                    .copy_from_slice(src_ppn.get_bytes_array());
+    /// where the program headers of the loaded elf are readable, e.g. for `AT_PHDR`
+    pub fn program_headers(&self) -> Option<ProgramHeaders> {
+        self.program_headers
+    }
+
+    pub fn heap_start(&self) -> Option<VirtAddr> {
+        self.heap.map(|(start, _)| start)
+    }
+
+    pub fn brk(&self) -> Option<VirtAddr> {
+        self.heap.map(|(_, brk)| brk)
+    }
+
+    /// Move the break to `new_brk` and return the old one. `None` if there is no heap,
+    /// `new_brk` lies below its start, the heap is not a user area, the grown heap would leave
+    /// user space, overlap an area or its pages cannot be committed. Other harts must flush
+    /// their tlb if the heap shrank.
+    pub fn set_brk(&mut self, new_brk: VirtAddr) -> Option<VirtAddr> {
+        let (start, old_brk) = self.heap?;
+        if new_brk < start {
+            return None;
+        }
+        let (old_end, new_end) = (old_brk.ceil(), new_brk.ceil());
+        let heap_end = old_end.max(new_end);
+        if heap_end > start.floor() && !self.is_user_range(start.floor(), heap_end) {
+            return None;
+        }
+        if new_end > old_end {
+            let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+            if self.overlaps_any(old_end, new_end) || !self.may_commit(new_end.0 - old_end.0, perm)
+            {
+                return None;
             }
+            self.append_to(start, new_brk);
+        } else if new_end < old_end {
+            self.settle_faults();
+            self.shrink_to(start, new_brk);
         }
+        self.heap = Some((start, new_brk));
+        Some(old_brk)
+    }
 
-        memory_set
+    /// Move the break by `delta` bytes and return the old one, see `set_brk`.
+    pub fn sbrk(&mut self, delta: isize) -> Option<VirtAddr> {
+        let (_, brk) = self.heap?;
+        let new_brk = brk.0.checked_add_signed(delta)?;
+        self.set_brk(VirtAddr(new_brk))
     }
 
-    /// Include sections in elf and trampoline and TrapContext and user stack,
-    /// also returns user_sp and entry point.
-    pub fn from_elf(
-        elf_data: &[u8],
-        trampline_start_va: usize,
-        trampline_start_pa: usize,
-        trap_cx_start_va: usize,
-        user_stack_size: usize,
-    ) -> (MemorySet, usize, usize) {
-        let mut memory_set_builder =
-            MemorySetBuilder::new().map_trampoline(trampline_start_va, trampline_start_pa);
+    /// Map the frames of the area `handle` into `target` at `target_va` with `perm`, e.g. a
+    /// ring buffer seen by both a driver and the kernel, and return the handle of the mirror.
+    /// Both areas are cloned by sharing. Each space unmaps its side on its own, the frames
+    /// are freed once neither maps them. `None` if the area does not exist, is not fully
+    /// resident, `target_va` is not page-aligned, the range is taken in `target` or `target`
+    /// refuses `perm`, e.g. by forbidding W^X.
+    pub fn mirror_area_into(
+        &mut self,
+        handle: AreaHandle,
+        target: &mut MemorySet,
+        target_va: VirtAddr,
+        perm: MapPermission,
+    ) -> Option<AreaHandle> {
+        self.settle_faults();
+        let area = self.areas.iter_mut().find(|area| area.handle() == handle)?;
+        let pages = area.page_count();
+        let start = target_va.floor();
+        let end = VirtPageNum(start.0 + pages);
+        if area.map_type().is_direct()
+            || self.frames.count(area.vpn_range) != pages
+            || !target_va.aligned()
+            || end.0 > USER_SPACE_PAGES
+            || target.overlaps_any(start, end)
+        {
+            return None;
+        }
+        let frames = self
+            .frames
+            .pages(area.vpn_range)
+            .filter_map(|(_, info)| info.frame().cloned())
+            .collect::<Vec<_>>();
+        let mirror = MapArea::new(start.into(), end.into(), MapType::Framed, perm)
+            .with_clone_strategy(CloneStrategy::Share);
+        target.validate_push(&mirror).ok()?;
+        area.set_clone_strategy(CloneStrategy::Share);
+        let mirror_handle = mirror.handle();
+        target.push_with_frames(mirror, frames);
+        self.mirrors.push((handle, mirror_handle));
+        target.mirrors.push((mirror_handle, handle));
+        Some(mirror_handle)
+    }
 
-        // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
-        let ph_count = elf_header.pt2.ph_count();
-        let mut max_end_vpn = VirtPageNum(0);
-        for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
-                let map_perm = Self::get_map_perm(ph.flags());
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
-
-                memory_set_builder = memory_set_builder.push_framed_with_data(
-                    start_va.into(),
-                    end_va.into(),
-                    map_perm,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
-            }
+    /// Map the frames of the shared area `handle` of `source` at `va` with `perm`, e.g. to
+    /// attach a shm segment or a pipe buffer, and return the handle of the new area. The
+    /// frames are freed once no memory set maps them. `None` if `source` has no shared area
+    /// `handle`, `va` is not page-aligned or the range is taken.
+    pub fn map_shared(
+        &mut self,
+        source: &MemorySet,
+        handle: AreaHandle,
+        va: VirtAddr,
+        perm: MapPermission,
+    ) -> Option<AreaHandle> {
+        let area = source
+            .areas
+            .iter()
+            .find(|area| area.handle() == handle && area.map_type() == MapType::Shared)?;
+        let start = va.floor();
+        let end = VirtPageNum(start.0 + area.page_count());
+        if !va.aligned() || end.0 > USER_SPACE_PAGES || self.overlaps_any(start, end) {
+            return None;
         }
-        // map user stack with U flags
-        let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_bottom: usize = max_end_va.into();
-        // guard page
-        user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + user_stack_size;
+        let frames = source
+            .frames
+            .pages(area.vpn_range)
+            .filter_map(|(_, info)| info.frame().cloned())
+            .collect::<Vec<_>>();
+        let shared = MapArea::new(start.into(), end.into(), MapType::Shared, perm);
+        let shared_handle = shared.handle();
+        self.push_with_frames(shared, frames);
+        Some(shared_handle)
+    }
 
-        let rwu = MapPermission::R | MapPermission::W | MapPermission::U;
-        let rw = MapPermission::R | MapPermission::W;
-        let memory_set = memory_set_builder
-            .push_framed(user_stack_bottom, user_stack_top, rwu)
-            .push_framed(user_stack_top, user_stack_top, rwu)
-            .push_framed(trap_cx_start_va, trampline_start_va, rw)
-            .build();
-
-        (
-            memory_set,
-            user_stack_top,
-            elf.header.pt2.entry_point() as usize,
+    /// Allocate the frames of a ring buffer of `len` bytes once and map them readable and
+    /// writable by the user into both `producer` and `consumer`, each in a free region, see
+    /// `find_free_area`. Returns the addresses of the ring in the producer and the consumer.
+    pub fn create_shared_ring(
+        producer: &mut MemorySet,
+        consumer: &mut MemorySet,
+        len: usize,
+    ) -> Option<(VirtAddr, VirtAddr)> {
+        let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+        let producer_va = producer.find_free_area(len, PAGE_SIZE, None)?;
+        let consumer_va = consumer.find_free_area(len, PAGE_SIZE, None)?;
+        let ring = MapArea::new(
+            producer_va,
+            VirtAddr(producer_va.0 + len),
+            MapType::Framed,
+            perm,
         )
+        .with_origin(AreaOrigin::Mmap);
+        let handle = ring.handle();
+        producer.push(ring, None);
+        producer.mirror_area_into(handle, consumer, consumer_va, perm)?;
+        Some((producer_va, consumer_va))
+    }
+
+    /// areas still mapped that share their frames with an area of another memory set,
+    /// paired with the handle of that area
+    pub fn mirrors(&self) -> impl Iterator<Item = (AreaHandle, AreaHandle)> + '_ {
+        self.mirrors
+            .iter()
+            .copied()
+            .filter(|(handle, _)| self.areas.iter().any(|area| area.handle() == *handle))
+    }
+
+    /// Resize the area spanning exactly `old_start..old_start + old_len` to `new_len` bytes,
+    /// like `mremap`, and return its new start. It is resized in place if possible, otherwise
+    /// or if `new_addr` asks for it, its frames move to a free range, or to `new_addr`,
+    /// without copying the pages. `None` if there is no such area, it is identical or not a
+    /// user area, there is no room in user space, or grown pages cannot be committed. Other
+    /// harts must flush their tlb afterwards.
+    pub fn remap(
+        &mut self,
+        old_start: VirtAddr,
+        old_len: usize,
+        new_len: usize,
+        new_addr: Option<VirtAddr>,
+    ) -> Option<VirtAddr> {
+        if !old_start.aligned() || new_len == 0 || matches!(new_addr, Some(va) if !va.aligned()) {
+            return None;
+        }
+        self.settle_faults();
+        let old_end = VirtAddr(old_start.0.checked_add(old_len)?).ceil();
+        let idx = self.areas.iter().position(|area| {
+            area.vpn_range.get_start() == old_start.floor() && area.vpn_range.get_end() == old_end
+        })?;
+        let area = &self.areas[idx];
+        if area.map_type().is_direct() || !self.is_user_range(old_start.floor(), old_end) {
+            return None;
+        }
+        let old_pages = area.page_count();
+        let new_pages = new_len.div_ceil(PAGE_SIZE);
+        let perm = if area.allows(AccessType::Write) {
+            MapPermission::W
+        } else {
+            MapPermission::empty()
+        };
+        if new_pages > old_pages && !self.may_commit(new_pages - old_pages, perm) {
+            return None;
+        }
+
+        let in_place = new_addr.is_none() || new_addr == Some(old_start);
+        let grown_end = VirtPageNum(old_start.floor().0 + new_pages);
+        if in_place
+            && (new_pages <= old_pages
+                || (self.is_user_range(old_end, grown_end)
+                    && !self.overlaps_any(old_end, grown_end)))
+        {
+            let new_end = VirtAddr::from(grown_end);
+            match new_pages.cmp(&old_pages) {
+                Ordering::Less => {
+                    self.shrink_to(old_start, new_end);
+                }
+                Ordering::Greater => {
+                    self.append_to(old_start, new_end);
+                }
+                Ordering::Equal => {}
+            }
+            return Some(old_start);
+        }
+
+        let new_start = match new_addr {
+            Some(va) => {
+                let end = VirtPageNum(va.floor().0 + new_pages);
+                if !self.is_user_range(va.floor(), end) || self.overlaps_any(va.floor(), end) {
+                    return None;
+                }
+                va
+            }
+            None => self.find_free_area(new_len, PAGE_SIZE, None)?,
+        };
+        if new_pages < old_pages {
+            self.shrink_to(
+                old_start,
+                VirtPageNum(old_start.floor().0 + new_pages).into(),
+            );
+        }
+        let area = &mut self.areas[idx];
+        self.fault_around.retire(&self.page_table, area.vpn_range);
+        area.move_to(&mut self.page_table, &mut self.frames, new_start.floor());
+        let moved = area.vpn_range;
+        self.page_table_usage.record_range(moved);
+        if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+            soft_dirty.extend(moved);
+        }
+        if new_pages > old_pages {
+            self.append_to(
+                new_start,
+                VirtPageNum(new_start.floor().0 + new_pages).into(),
+            );
+        }
+        Some(new_start)
+    }
+
+    /// unmap `start..end`, areas sticking out of the range are split and keep the rest
+    fn unmap_pages(&mut self, start: VirtPageNum, end: VirtPageNum) {
+        self.settle_faults();
+        for at in [start, end] {
+            if let Some(idx) = self
+                .areas
+                .iter()
+                .position(|area| area.vpn_range.get_start() < at && at < area.vpn_range.get_end())
+            {
+                let tail = self.areas[idx].split_off(at);
+                self.areas.push(tail);
+            }
+        }
+        while let Some(idx) = self.areas.iter().position(|area| {
+            start <= area.vpn_range.get_start()
+                && area.vpn_range.get_end() <= end
+                && area.page_count() > 0
+        }) {
+            let mut area = self.areas.remove(idx);
+            self.fault_around.retire(&self.page_table, area.vpn_range);
+            area.unmap(&mut self.page_table, &mut self.frames);
+        }
+        self.debug_assert_invariants();
+    }
+
+    /// Hand `range` to a child manager, `None` if it leaves user space or overlaps an area,
+    /// the trampoline, a fixed mapping or another delegation.
+    pub fn delegate_range(&mut self, range: VPNRange) -> Option<DelegatedRegion> {
+        let (start, end) = (range.get_start(), range.get_end());
+        if end.0 > USER_SPACE_PAGES
+            || self.overlaps_any(start, end)
+            || self.fixed_in(start, end)
+            || self
+                .delegations
+                .values()
+                .any(|delegated| delegated.get_start() < end && start < delegated.get_end())
+        {
+            return None;
+        }
+        let id = self.next_delegation;
+        self.next_delegation += 1;
+        self.delegations.insert(id, range);
+        Some(DelegatedRegion::new(id, range))
+    }
+
+    /// Map an area on behalf of the manager of `region`, fails if the region was revoked,
+    /// the area does not lie within it, overlaps another area, would be a user-accessible
+    /// kernel area or writable and executable while forbidden, or frames run out.
+    pub fn map_delegated(
+        &mut self,
+        region: &DelegatedRegion,
+        map_area: MapArea,
+        data: Option<&[u8]>,
+    ) -> bool {
+        let (start, end) = (map_area.vpn_range.get_start(), map_area.vpn_range.get_end());
+        if !self.delegations.contains_key(&region.id())
+            || !region.covers(start, end)
+            || self.overlaps_any(start, end)
+            || self.validate_push(&map_area).is_err()
+        {
+            return false;
+        }
+        self.try_push(map_area, data, AllocPolicy::Block).is_ok()
+    }
+
+    /// Take back a delegated range, everything mapped into it is unmapped and the tlb flushed.
+    pub fn revoke_delegation(&mut self, region: DelegatedRegion) {
+        if self.delegations.remove(&region.id()).is_none() {
+            return;
+        }
+        let starts = self
+            .areas
+            .iter()
+            .map(|area| area.vpn_range)
+            .filter(|range| region.covers(range.get_start(), range.get_end()))
+            .map(|range| range.get_start())
+            .collect::<Vec<_>>();
+        for start in starts {
+            self.remove_area_with_start_vpn(start);
+        }
+        hw::flush_tlb();
+    }
+
+    /// whether any area intersects `start..end`
+    fn overlaps_any(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        start < end
+            && self
+                .areas
+                .iter()
+                .any(|area| area.vpn_range.get_start() < end && start < area.vpn_range.get_end())
+    }
+
+    /// whether `start..end` lies in user space clear of the trampoline and the fixed mappings,
+    /// and every area intersecting it is a user area, so that the user may reshape it
+    fn is_user_range(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        start < end
+            && end.0 <= USER_SPACE_PAGES
+            && !self.fixed_in(start, end)
+            && self
+                .areas
+                .iter()
+                .filter(|area| area.vpn_range.get_start() < end && start < area.vpn_range.get_end())
+                .all(|area| area.class() == AreaClass::User)
+    }
+
+    /// Map a framed area of `len` bytes at `start_va` for as long as the returned guard lives,
+    /// e.g. a transient kernel window to copy an elf from a staging buffer.
+    pub fn map_temporary(
+        &mut self,
+        start_va: VirtAddr,
+        len: usize,
+        perm: MapPermission,
+    ) -> TempAreaGuard<'_> {
+        let map_area = MapArea::new(start_va, VirtAddr(start_va.0 + len), MapType::Framed, perm)
+            .with_origin(AreaOrigin::KernelInternal);
+        let start_vpn = map_area.vpn_range.get_start();
+        self.push(map_area, None);
+        TempAreaGuard::new(self, start_vpn)
+    }
+
+    /// `push` that reports running out of frames instead of panicking, with
+    /// `AllocPolicy::NoBlock` the frames come from the scratch only, see `try_transaction`
+    pub fn try_push(
+        &mut self,
+        map_area: MapArea,
+        data: Option<&[u8]>,
+        policy: AllocPolicy,
+    ) -> Result<(), TransactionError> {
+        self.try_transaction(policy, |txn| txn.push(map_area, data))
+    }
+
+    /// push an area backed by the given frames, one per page in ascending order,
+    /// e.g. frames shared with another memory set
+    pub fn push_with_frames(&mut self, mut map_area: MapArea, frames: Vec<Arc<FrameTracker>>) {
+        Self::check_class(&map_area);
+        self.check_wx(&map_area);
+        map_area.map_frames(&mut self.page_table, &mut self.frames, frames);
+        if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+            soft_dirty.extend(map_area.vpn_range);
+        }
+        self.push_mapped(map_area);
+    }
+
+    /// remove the area starting at `start_vpn`, returns false if there is none
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) -> bool {
+        match self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == start_vpn)
+        {
+            Some(idx) => {
+                self.remove_area_at(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// remove the area containing `va`, returns false if there is none
+    pub fn remove_area(&mut self, va: VirtAddr) -> bool {
+        match self.area_index(va.floor()) {
+            Some(idx) => {
+                self.remove_area_at(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_area_at(&mut self, idx: usize) {
+        let area = &mut self.areas[idx];
+        self.fault_around.retire(&self.page_table, area.vpn_range);
+        area.unmap(&mut self.page_table, &mut self.frames);
+        self.areas.remove(idx);
+        self.debug_assert_invariants();
+    }
+
+    /// the area containing `va`
+    pub fn find_area(&self, va: VirtAddr) -> Option<&MapArea> {
+        self.area_index(va.floor()).map(|idx| &self.areas[idx])
+    }
+
+    pub fn find_area_mut(&mut self, va: VirtAddr) -> Option<&mut MapArea> {
+        self.area_index(va.floor()).map(|idx| &mut self.areas[idx])
+    }
+
+    /// index of the area containing `vpn`, or starting at it so that empty areas,
+    /// e.g. a heap without pages yet, are found as well
+    fn area_index(&self, vpn: VirtPageNum) -> Option<usize> {
+        self.areas
+            .iter()
+            .position(|area| area.contains(vpn))
+            .or_else(|| {
+                self.areas
+                    .iter()
+                    .position(|area| area.vpn_range.get_start() == vpn)
+            })
+    }
+
+    /// Apply the changes staged by `f` all at once, or none of them if staging fails, an area
+    /// would cover the trampoline or a fixed mapping, or the frames for the framed pages
+    /// cannot be reserved. Page table nodes are still allocated
+    /// while committing.
+    pub fn transaction<'a, F>(&mut self, f: F) -> Result<(), TransactionError>
+    where
+        F: FnOnce(&mut Transaction<'a>) -> Result<(), TransactionError>,
+    {
+        self.try_transaction(AllocPolicy::Block, f)
+    }
+
+    /// `transaction`, but with `AllocPolicy::NoBlock` the frames are taken from the scratch
+    /// and `TransactionError::WouldBlock` is returned if it holds too few, nothing is applied then
+    pub fn try_transaction<'a, F>(
+        &mut self,
+        policy: AllocPolicy,
+        f: F,
+    ) -> Result<(), TransactionError>
+    where
+        F: FnOnce(&mut Transaction<'a>) -> Result<(), TransactionError>,
+    {
+        let mut txn = Transaction::new(
+            self.areas
+                .iter()
+                .map(|area| (area.vpn_range, area.map_type()))
+                .collect(),
+        );
+        f(&mut txn)?;
+        let (ops, frames_needed) = txn.into_ops();
+        // pushing checks each area again and panics, nothing may be committed by then
+        for op in ops.iter() {
+            match op {
+                StagedOp::Push(map_area, _) => {
+                    let range = map_area.vpn_range;
+                    if self.fixed_in(range.get_start(), range.get_end()) {
+                        return Err(TransactionError::Overlap);
+                    }
+                    self.validate_push(map_area)?;
+                }
+                StagedOp::Append(start_vpn, new_end) if self.fixed_in(*start_vpn, *new_end) => {
+                    return Err(TransactionError::Overlap);
+                }
+                _ => {}
+            }
+        }
+        let mut reserved = Vec::with_capacity(frames_needed);
+        match policy {
+            AllocPolicy::Block => {
+                for _ in 0..frames_needed {
+                    // frames reserved so far are freed when dropped
+                    reserved.push(Arc::new(
+                        frame_alloc().ok_or(TransactionError::OutOfMemory)?,
+                    ));
+                }
+            }
+            AllocPolicy::NoBlock(scratch) => {
+                if scratch.len() < frames_needed {
+                    return Err(TransactionError::WouldBlock);
+                }
+                reserved.extend((0..frames_needed).map(|_| scratch.take().unwrap()));
+            }
+        }
+
+        self.settle_faults();
+        for op in ops {
+            match op {
+                StagedOp::Push(map_area, data) => {
+                    if !map_area.map_type().is_framed() {
+                        self.push(map_area, data);
+                        continue;
+                    }
+                    let frames = reserved.split_off(reserved.len() - map_area.page_count());
+                    self.push_with_frames(map_area, frames);
+                    if let Some(data) = data {
+                        self.areas
+                            .last_mut()
+                            .unwrap()
+                            .copy_data(&mut self.page_table, data);
+                    }
+                }
+                StagedOp::Remove(start_vpn) => {
+                    self.remove_area_with_start_vpn(start_vpn);
+                }
+                StagedOp::Shrink(start_vpn, new_end) => {
+                    self.shrink_to(start_vpn.into(), new_end.into());
+                }
+                StagedOp::Append(start_vpn, new_end) => {
+                    let area = self
+                        .areas
+                        .iter_mut()
+                        .find(|area| area.vpn_range.get_start() == start_vpn)
+                        .unwrap();
+                    let old_end = area.vpn_range.get_end();
+                    let frames = if area.map_type().is_framed() {
+                        reserved.split_off(reserved.len() - (new_end.0 - old_end.0))
+                    } else {
+                        Vec::new()
+                    };
+                    area.append_with_frames(
+                        &mut self.page_table,
+                        &mut self.frames,
+                        new_end,
+                        frames,
+                    );
+                    self.page_table_usage
+                        .record_range(VPNRange::new(old_end, new_end));
+                    if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+                        soft_dirty.extend(VPNRange::new(old_end, new_end));
+                    }
+                }
+            }
+        }
+        self.debug_assert_invariants();
+        Ok(())
+    }
+
+    pub fn recycle_data_pages(&mut self) {
+        self.fault_around.retire_all(&self.page_table);
+        self.areas.clear();
+        self.frames = FrameTable::new();
+    }
+
+    /// Free up to `max_pages` frames whose content can be recovered without backing store,
+    /// to respond to memory pressure: pages of lazy areas populated ahead of a fault and never
+    /// accessed, and pages of lazy areas holding only zeros. They are unmapped and populated
+    /// again on the next access. Returns the number of frames freed, other harts must flush
+    /// their tlb before the memory set runs there again.
+    pub fn release_clean_pages(&mut self, max_pages: usize) -> usize {
+        self.settle_faults();
+        let mut released = 0;
+        for area in self.areas.iter_mut() {
+            if released == max_pages {
+                break;
+            }
+            if area.map_type() != MapType::Lazy {
+                continue;
+            }
+            let clean = self
+                .frames
+                .pages(area.vpn_range)
+                .filter(|(vpn, info)| {
+                    Self::is_clean(&self.fault_around, &self.page_table, *vpn, info)
+                })
+                .map(|(vpn, _)| vpn)
+                .take(max_pages - released)
+                .collect::<Vec<_>>();
+            for vpn in clean {
+                self.fault_around
+                    .retire(&self.page_table, VPNRange::new(vpn, VirtPageNum(vpn.0 + 1)));
+                area.unmap_one(&mut self.page_table, &mut self.frames, vpn);
+                released += 1;
+            }
+        }
+        released
     }
 
-    fn get_map_perm(ph_flags: Flags) -> MapPermission {
-        let mut map_perm = MapPermission::U;
-        if ph_flags.is_read() {
-            map_perm |= MapPermission::R;
+    /// Same as `release_clean_pages`, but examines at most `budget` resident pages of lazy
+    /// areas from `from` on. Returns the number of frames freed, the number of pages examined
+    /// and the page to resume from, `None` once the end of the address space is reached.
+    pub(crate) fn release_clean_pages_from(
+        &mut self,
+        from: VirtPageNum,
+        budget: usize,
+    ) -> (usize, usize, Option<VirtPageNum>) {
+        if budget == 0 {
+            return (0, 0, Some(from));
+        }
+        self.settle_faults();
+        let mut order = (0..self.areas.len())
+            .filter(|idx| {
+                let area = &self.areas[*idx];
+                area.map_type() == MapType::Lazy && area.vpn_range.get_end() > from
+            })
+            .collect::<Vec<_>>();
+        order.sort_by_key(|idx| self.areas[*idx].vpn_range.get_start());
+
+        let (mut released, mut examined) = (0, 0);
+        for idx in order {
+            let area = &mut self.areas[idx];
+            let pages = self
+                .frames
+                .pages(area.vpn_range)
+                .filter(|(vpn, _)| *vpn >= from)
+                .take(budget - examined)
+                .map(|(vpn, info)| {
+                    (
+                        vpn,
+                        Self::is_clean(&self.fault_around, &self.page_table, vpn, info),
+                    )
+                })
+                .collect::<Vec<_>>();
+            for (vpn, clean) in pages {
+                examined += 1;
+                if clean {
+                    self.fault_around
+                        .retire(&self.page_table, VPNRange::new(vpn, VirtPageNum(vpn.0 + 1)));
+                    area.unmap_one(&mut self.page_table, &mut self.frames, vpn);
+                    released += 1;
+                }
+                if examined == budget {
+                    return (released, examined, Some(VirtPageNum(vpn.0 + 1)));
+                }
+            }
         }
-        if ph_flags.is_write() {
-            map_perm |= MapPermission::W;
+        (released, examined, None)
+    }
+
+    /// Free up to `n_pages` frames of user-accessible framed and lazy areas, picking victims
+    /// by a clock over their resident pages: pages accessed since the hand last passed them
+    /// get a second chance, the others are dropped if they can be populated again on demand,
+    /// see `release_clean_pages`, or evicted to the swap backend, see `evict`. Returns the
+    /// number of frames freed, other harts must flush their tlb.
+    pub fn reclaim(&mut self, n_pages: usize) -> usize {
+        self.settle_faults();
+        let resident = self
+            .areas
+            .iter()
+            .filter(|area| Self::is_reclaimable(area))
+            .flat_map(|area| {
+                self.frames
+                    .pages(area.vpn_range)
+                    .filter(|(_, info)| info.frame().is_some())
+                    .map(|(vpn, _)| vpn)
+            })
+            .collect::<Vec<_>>();
+        self.clock.extend(resident);
+
+        let mut released = 0;
+        // every page is passed at most twice, once to clear its accessed bit
+        let mut steps = 2 * self.clock.len();
+        while released < n_pages && steps > 0 {
+            steps -= 1;
+            let Some(vpn) = self.clock.advance() else {
+                break;
+            };
+            let Some(area) = self
+                .areas
+                .iter_mut()
+                .find(|area| area.contains(vpn))
+                .filter(|area| Self::is_reclaimable(area))
+            else {
+                continue;
+            };
+            let Some(info) = self.frames.get(vpn).filter(|info| info.frame().is_some()) else {
+                continue;
+            };
+            if pte_ext::take_accessed_dirty(&self.page_table, vpn, PTEFlags::A)
+                .is_some_and(|taken| !taken.is_empty())
+            {
+                self.clock.requeue(vpn);
+                continue;
+            }
+            if area.map_type() == MapType::Lazy
+                && Self::is_clean(&self.fault_around, &self.page_table, vpn, info)
+            {
+                self.fault_around
+                    .retire(&self.page_table, VPNRange::new(vpn, VirtPageNum(vpn.0 + 1)));
+                area.unmap_one(&mut self.page_table, &mut self.frames, vpn);
+                released += 1;
+            } else if self.evict(vpn) {
+                released += 1;
+            } else {
+                self.clock.requeue(vpn);
+            }
         }
-        if ph_flags.is_execute() {
-            map_perm |= MapPermission::X;
+        self.debug_assert_invariants();
+        released
+    }
+
+    fn is_reclaimable(area: &MapArea) -> bool {
+        matches!(area.map_type(), MapType::Framed | MapType::Lazy) && area.allows_user()
+    }
+
+    /// whether the frame of a page of a lazy area can be freed and populated again on demand
+    fn is_clean(
+        fault_around: &FaultAround,
+        page_table: &PageTable,
+        vpn: VirtPageNum,
+        info: &PageInfo,
+    ) -> bool {
+        info.state() == PageState::Resident
+            && !info.is_shared()
+            && (fault_around.is_untouched(page_table, vpn)
+                || info
+                    .frame()
+                    .is_some_and(|frame| frame.ppn.get_bytes_array().iter().all(|byte| *byte == 0)))
+    }
+
+    /// Tear down the memory set in two phases so that the trampoline stays mapped until the
+    /// final trap return. Phase 1, done here, unmaps all areas and frees their frames,
+    /// phase 2 releases the trampoline and the page table when the returned guard is finished.
+    pub fn teardown_in_stages(mut self) -> Teardown {
+        self.fault_around.retire_all(&self.page_table);
+        for area in self.areas.iter_mut() {
+            area.unmap(&mut self.page_table, &mut self.frames);
         }
+        self.areas.clear();
 
-        map_perm
+        Teardown::new(self)
+    }
+
+    /// Switch the calling hart `hart_id` to the memory set, does nothing without the
+    /// `riscv-hw` feature. The whole tlb is flushed unless it has an asid, the entries of its
+    /// asid are flushed if the hart activates it for the first time or a page lost a mapping
+    /// or permissions since the hart last activated it, so harts that ran it before never
+    /// reuse stale entries. Panics if its canary is broken, see `enable_canary`.
+    pub fn activate(&self, hart_id: usize) {
+        assert!(self.verify(), "canary of the memory set is broken");
+        hw::activate(self.token(), self.asid, self.note_activation(hart_id));
+    }
+
+    /// record that `hart_id` activates the memory set, returns whether its tlb entries of
+    /// the asid may be stale
+    fn note_activation(&self, hart_id: usize) -> bool {
+        let generation = pte_ext::flush_generation(&self.page_table);
+        self.activated_at.lock().insert(hart_id, generation) != Some(generation)
+    }
+
+    /// Like `activate`, but first check that the root page table is still registered by
+    /// this memory set, see `registry::register`, and that the trampoline, the fixed
+    /// mappings and the canary are intact, so that a corrupted token is never installed.
+    pub fn activate_checked(&self, hart_id: usize) -> Result<(), ActivateError> {
+        match registry::generation_of(self.token()) {
+            None => return Err(ActivateError::NotRegistered),
+            Some(generation) if generation != self.generation => {
+                return Err(ActivateError::StaleRoot)
+            }
+            Some(_) => {}
+        }
+        let intact = |vpn: VirtPageNum| {
+            pte_ext::translate(&self.page_table, vpn).filter(|pte| {
+                pte.is_valid()
+                    && pte.flags() & (PTEFlags::R | PTEFlags::W | PTEFlags::X | PTEFlags::U)
+                        == PTEFlags::R | PTEFlags::X
+            })
+        };
+        if self.trampoline.is_some_and(|vpn| intact(vpn).is_none())
+            || self
+                .fixed_mappings
+                .iter()
+                .any(|mapping| !intact(mapping.vpn).is_some_and(|pte| pte.ppn() == mapping.ppn))
+        {
+            return Err(ActivateError::TrampolineBroken);
+        }
+        if !self.verify() {
+            return Err(ActivateError::CanaryBroken);
+        }
+        hw::activate(self.token(), self.asid, self.note_activation(hart_id));
+        Ok(())
+    }
+
+    /// stamp of the memory set, unique among all memory sets created
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Write the areas, their ranges, types, permissions, clone strategies and classes, the
+    /// trampoline and the contents of all mapped pages to `write`, so that `restore_layout`
+    /// can rebuild an equivalent memory set later, e.g. to checkpoint or migrate a process.
+    /// The canary, fixed mappings, heap, tags, origins, asid and policies are not captured.
+    pub fn serialize_layout<W: FnMut(&[u8])>(&self, mut write: W) {
+        write(&checkpoint::MAGIC);
+        write(&WIRE_VERSION.to_le_bytes());
+        match self.trampoline {
+            Some(vpn) => {
+                let ppn = pte_ext::translate(&self.page_table, vpn).unwrap().ppn();
+                write(&[1]);
+                write(&(vpn.0 as u64).to_le_bytes());
+                write(&(ppn.0 as u64).to_le_bytes());
+            }
+            None => write(&[0]),
+        }
+
+        let canary_vpn = self.canary.map(|canary| canary.va().floor());
+        let areas = self
+            .areas
+            .iter()
+            .filter(|area| !canary_vpn.is_some_and(|vpn| area.contains(vpn)))
+            .collect::<Vec<_>>();
+        write(&(areas.len() as u64).to_le_bytes());
+        for area in areas {
+            write(&(area.vpn_range.get_start().0 as u64).to_le_bytes());
+            write(&(area.vpn_range.get_end().0 as u64).to_le_bytes());
+            write(&[
+                area.map_type().to_wire(),
+                area.permission().to_wire(),
+                area.clone_strategy().to_wire(),
+                (area.class() == AreaClass::Kernel) as u8,
+            ]);
+            if let Some(ppn) = area.phys_start() {
+                write(&(ppn.0 as u64).to_le_bytes());
+            }
+            if area.map_type().is_direct() {
+                continue;
+            }
+            for vpn in area.vpn_range {
+                let swap_slot = self.frames.get(vpn).and_then(|info| info.swap_slot());
+                match pte_ext::translate(&self.page_table, vpn).filter(|pte| pte.is_valid()) {
+                    Some(pte) => {
+                        write(&[1]);
+                        write(pte.ppn().get_bytes_array());
+                    }
+                    None if swap_slot.is_some() => {
+                        let mut page = [0; PAGE_SIZE];
+                        swap_slot.unwrap().read(&mut page);
+                        write(&[1]);
+                        write(&page);
+                    }
+                    None => write(&[0]),
+                }
+            }
+        }
+    }
+
+    /// rebuild a memory set from a layout written by `serialize_layout`, with fresh frames
+    pub fn restore_layout(data: &[u8]) -> Result<Self, CheckpointError> {
+        let mut reader = checkpoint::Reader::new(data);
+        if reader.bytes(checkpoint::MAGIC.len())? != checkpoint::MAGIC {
+            return Err(CheckpointError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != WIRE_VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+
+        let mut memory_set = Self::new_bare();
+        if reader.u8()? != 0 {
+            let vpn = VirtPageNum(reader.u64()? as usize);
+            let ppn = PhysPageNum(reader.u64()? as usize);
+            memory_set.map_trampoline(vpn, ppn);
+        }
+        for _ in 0..reader.u64()? {
+            let start_vpn = VirtPageNum(reader.u64()? as usize);
+            let end_vpn = VirtPageNum(reader.u64()? as usize);
+            if end_vpn < start_vpn {
+                return Err(CheckpointError::Map(TransactionError::InvalidRange));
+            }
+            let map_type = MapType::from_wire(reader.u8()?).ok_or(CheckpointError::BadCode)?;
+            let perm = MapPermission::from_wire(reader.u8()?).ok_or(CheckpointError::BadCode)?;
+            let clone_strategy =
+                CloneStrategy::from_wire(reader.u8()?).ok_or(CheckpointError::BadCode)?;
+            let class = match reader.u8()? {
+                0 => AreaClass::User,
+                1 if !perm.contains(MapPermission::U) => AreaClass::Kernel,
+                _ => return Err(CheckpointError::BadCode),
+            };
+            let map_area = match map_type {
+                MapType::Mmio => MapArea::new_mmio(
+                    start_vpn.into(),
+                    end_vpn.into(),
+                    PhysPageNum(reader.u64()? as usize).into(),
+                    perm,
+                ),
+                _ => MapArea::new(start_vpn.into(), end_vpn.into(), map_type, perm),
+            }
+            .with_clone_strategy(clone_strategy)
+            .with_class(class);
+            memory_set
+                .try_push(map_area, None, AllocPolicy::Block)
+                .map_err(CheckpointError::Map)?;
+            if map_type.is_direct() {
+                continue;
+            }
+
+            for vpn in VPNRange::new(start_vpn, end_vpn) {
+                if reader.u8()? == 0 {
+                    continue;
+                }
+                let page = reader.bytes(PAGE_SIZE)?;
+                if map_type == MapType::Lazy {
+                    if !memory_set.areas.last_mut().unwrap().fill_page(
+                        &mut memory_set.page_table,
+                        &mut memory_set.frames,
+                        vpn,
+                        page,
+                    ) {
+                        return Err(CheckpointError::Map(TransactionError::OutOfMemory));
+                    }
+                } else {
+                    pte_ext::translate(&memory_set.page_table, vpn)
+                        .unwrap()
+                        .ppn()
+                        .get_bytes_array()
+                        .copy_from_slice(page);
+                }
+            }
+        }
+        Ok(memory_set)
+    }
+
+    /// Map a canary page at a kernel-side va derived from `seed`, filled with a value
+    /// derived from it as well, and check it on `activate`. The returned `Canary` can check
+    /// a token kept elsewhere. `None` if a canary is mapped already or frames ran out.
+    pub fn enable_canary(&mut self, seed: u64) -> Option<Canary> {
+        if self.canary.is_some() {
+            return None;
+        }
+        let kernel_pages = USER_SPACE_PAGES - 1;
+        let mut vpn = VirtPageNum(USER_SPACE_PAGES + (canary::mix(seed) as usize) % kernel_pages);
+        while self.overlaps_any(vpn, VirtPageNum(vpn.0 + 1))
+            || self.fixed_in(vpn, VirtPageNum(vpn.0 + 1))
+        {
+            vpn = VirtPageNum(USER_SPACE_PAGES + (vpn.0 + 1 - USER_SPACE_PAGES) % kernel_pages);
+        }
+        let canary = Canary::new(vpn, canary::mix(seed ^ vpn.0 as u64) | 1);
+        let mut area = MapArea::new(
+            vpn.into(),
+            VirtPageNum(vpn.0 + 1).into(),
+            MapType::Framed,
+            MapPermission::R,
+        )
+        .with_origin(AreaOrigin::KernelInternal)
+        .with_class(AreaClass::Kernel)
+        .with_clone_strategy(CloneStrategy::Skip)
+        .with_keep_on_exec();
+        if !area.try_map_one(&mut self.page_table, &mut self.frames, vpn) {
+            return None;
+        }
+        let pte = pte_ext::translate(&self.page_table, vpn).unwrap();
+        canary.fill(pte.ppn().get_bytes_array());
+        self.push_mapped(area);
+        self.canary = Some(canary);
+        Some(canary)
+    }
+
+    pub fn canary(&self) -> Option<Canary> {
+        self.canary
+    }
+
+    /// whether the canary, if any, reads back intact through the page table
+    pub fn verify(&self) -> bool {
+        match self.canary {
+            Some(canary) => canary.check(self.token()),
+            None => true,
+        }
+    }
+
+    /// Run `f` with the per-page tlb flushes of unmapped and remapped pages deferred, and
+    /// flush the whole tlb of the calling hart once afterwards if any was, e.g. to tear down
+    /// many pages at once.
+    pub fn batch_tlb_flush<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        pte_ext::defer_flushes(&self.page_table);
+        let result = f(self);
+        if pte_ext::end_deferred_flushes(&self.page_table) {
+            hw::flush_tlb();
+        }
+        result
+    }
+
+    pub fn map_trampoline(&mut self, vpn: VirtPageNum, ppn: PhysPageNum) {
+        self.page_table.map(vpn, ppn, PTEFlags::R | PTEFlags::X);
+        self.page_table_usage.record(vpn);
+        self.trampoline = Some(vpn);
+    }
+
+    /// Map the kernel page `ppn` R+X at `vpn` under `name`, e.g. a trap vector per hart,
+    /// kept through fork and exec like the trampoline. Returns false if the name is in use
+    /// or the page collides with an area, the trampoline or another fixed mapping.
+    pub fn map_fixed(&mut self, name: &'static str, vpn: VirtPageNum, ppn: PhysPageNum) -> bool {
+        let end = VirtPageNum(vpn.0 + 1);
+        if self
+            .fixed_mappings
+            .iter()
+            .any(|mapping| mapping.name == name)
+            || self.overlaps_any(vpn, end)
+            || self.fixed_in(vpn, end)
+        {
+            return false;
+        }
+        self.page_table.map(vpn, ppn, PTEFlags::R | PTEFlags::X);
+        self.page_table_usage.record(vpn);
+        self.fixed_mappings.push(FixedMapping { name, vpn, ppn });
+        true
+    }
+
+    /// unmap the fixed mapping `name`, returns false if there is none
+    pub fn unmap_fixed(&mut self, name: &str) -> bool {
+        let Some(idx) = self
+            .fixed_mappings
+            .iter()
+            .position(|mapping| mapping.name == name)
+        else {
+            return false;
+        };
+        let mapping = self.fixed_mappings.remove(idx);
+        pte_ext::unmap(&mut self.page_table, mapping.vpn);
+        true
+    }
+
+    pub fn fixed_mappings(&self) -> &[FixedMapping] {
+        &self.fixed_mappings
+    }
+
+    /// the trampoline and the pages of the fixed mappings
+    fn fixed_pages(&self) -> impl Iterator<Item = VirtPageNum> + '_ {
+        self.trampoline
+            .into_iter()
+            .chain(self.fixed_mappings.iter().map(|mapping| mapping.vpn))
+    }
+
+    fn fixed_in(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        self.fixed_pages().any(|vpn| start <= vpn && vpn < end)
+    }
+
+    /// framed pages whose frame is also mapped by an identical area
+    pub fn physical_overlaps(&self) -> Vec<PhysicalOverlap> {
+        let identical = self
+            .areas
+            .iter()
+            .filter(|area| area.map_type() == MapType::Identical)
+            .collect::<Vec<_>>();
+        self.areas
+            .iter()
+            .flat_map(|area| self.frames.pages(area.vpn_range))
+            .filter_map(|(vpn, info)| info.frame().map(|frame| (vpn, frame.ppn)))
+            .filter(|(_, ppn)| {
+                identical
+                    .iter()
+                    .any(|area| area.contains(VirtPageNum(ppn.0)))
+            })
+            .map(|(vpn, ppn)| PhysicalOverlap { vpn, ppn })
+            .collect()
+    }
+
+    /// physical ranges declared by `MemorySetBuilder::allow_alias`
+    pub fn physical_aliases(&self) -> &[(PhysPageNum, PhysPageNum)] {
+        &self.physical_aliases
+    }
+
+    pub(crate) fn set_physical_aliases(&mut self, aliases: Vec<(PhysPageNum, PhysPageNum)>) {
+        self.physical_aliases = aliases;
+    }
+
+    /// Strip `perm` from all areas matching `filter` and flush the tlb once, e.g. to make a
+    /// compromised process non-executable. Returns the number of areas downgraded.
+    pub fn revoke<F>(&mut self, perm: MapPermission, filter: F) -> usize
+    where
+        F: Fn(&MapArea) -> bool,
+    {
+        self.settle_faults();
+        let revoked = self.batch_tlb_flush(|memory_set| {
+            let mut revoked = 0;
+            for area in memory_set.areas.iter_mut().filter(|area| filter(area)) {
+                area.remove_permission(&mut memory_set.page_table, perm);
+                revoked += 1;
+            }
+            revoked
+        });
+        self.debug_assert_invariants();
+        revoked
+    }
+
+    /// Unmap all areas tagged `tag`, e.g. stack, tls and trap context of an exiting thread.
+    /// Returns the number of areas unmapped, other harts must flush their tlb.
+    pub fn unmap_tagged(&mut self, tag: &str) -> usize {
+        self.settle_faults();
+        let mut unmapped = 0;
+        while let Some(idx) = self.areas.iter().position(|area| area.tag() == Some(tag)) {
+            let mut area = self.areas.remove(idx);
+            self.fault_around.retire(&self.page_table, area.vpn_range);
+            area.unmap(&mut self.page_table, &mut self.frames);
+            unmapped += 1;
+        }
+        self.debug_assert_invariants();
+        unmapped
+    }
+
+    /// Set the permission of all areas tagged `tag`, returns the number of areas changed,
+    /// other harts must flush their tlb. Nothing changes if `perm` would make a kernel or
+    /// mmio area user-accessible.
+    pub fn protect_tagged(&mut self, tag: &str, perm: MapPermission) -> usize {
+        if self.is_wx_forbidden(perm)
+            || self
+                .areas
+                .iter()
+                .any(|area| area.tag() == Some(tag) && !Self::may_protect(area, perm))
+        {
+            return 0;
+        }
+        self.settle_faults();
+        let mut protected = 0;
+        for area in self.areas.iter_mut().filter(|area| area.tag() == Some(tag)) {
+            area.set_permission(&mut self.page_table, perm);
+            protected += 1;
+        }
+        self.debug_assert_invariants();
+        protected
+    }
+
+    /// Change the permission of `start_va..end_va` to `perm`, like `mprotect`. Areas sticking
+    /// out of the range are split, and merged again with their neighbors once their
+    /// permissions match. Returns false if `start_va` is not page-aligned or a page of the
+    /// range is not mapped or `perm` would make a kernel or mmio area user-accessible,
+    /// nothing changes then. Other harts must flush their tlb afterwards.
+    pub fn protect(&mut self, start_va: VirtAddr, end_va: VirtAddr, perm: MapPermission) -> bool {
+        if !start_va.aligned() || end_va <= start_va || self.is_wx_forbidden(perm) {
+            return false;
+        }
+        let (start, end) = (start_va.floor(), end_va.ceil());
+        let mut covering = self
+            .areas
+            .iter()
+            .filter(|area| area.vpn_range.get_start() < end && start < area.vpn_range.get_end())
+            .map(|area| {
+                (
+                    area.vpn_range.get_start(),
+                    area.vpn_range.get_end(),
+                    Self::may_protect(area, perm),
+                )
+            })
+            .collect::<Vec<_>>();
+        if covering.iter().any(|(_, _, allowed)| !allowed) {
+            return false;
+        }
+        covering.sort();
+        let mut covered = start;
+        for (area_start, area_end, _) in covering {
+            if area_start > covered {
+                break;
+            }
+            covered = covered.max(area_end);
+        }
+        if covered < end {
+            return false;
+        }
+        self.settle_faults();
+        for at in [start, end] {
+            if let Some(idx) = self
+                .areas
+                .iter()
+                .position(|area| area.vpn_range.get_start() < at && at < area.vpn_range.get_end())
+            {
+                let tail = self.areas[idx].split_off(at);
+                self.areas.push(tail);
+            }
+        }
+        for area in self.areas.iter_mut().filter(|area| {
+            start <= area.vpn_range.get_start()
+                && area.vpn_range.get_end() <= end
+                && area.page_count() > 0
+        }) {
+            area.set_permission(&mut self.page_table, perm);
+        }
+        self.try_merge_around(start.into());
+        self.try_merge_around(VirtPageNum(end.0 - 1).into());
+        self.debug_assert_invariants();
+        true
+    }
+
+    /// Merge the area containing `va` with the areas right before and after it if they
+    /// differ in nothing but their range, e.g. after permissions were changed back.
+    /// Areas mirrored into other memory sets are left alone. Returns whether any merged.
+    pub fn try_merge_around(&mut self, va: VirtAddr) -> bool {
+        let Some(idx) = self.area_index(va.floor()) else {
+            return false;
+        };
+        let (start, end) = (
+            self.areas[idx].vpn_range.get_start(),
+            self.areas[idx].vpn_range.get_end(),
+        );
+        let merged_after = self.merge_at(end);
+        let merged_before = self.merge_at(start);
+        merged_after || merged_before
+    }
+
+    /// merge the non-empty areas ending and starting at `at` if they can be
+    fn merge_at(&mut self, at: VirtPageNum) -> bool {
+        let is_mirrored = |area: &MapArea| {
+            self.mirrors
+                .iter()
+                .any(|(local, _)| *local == area.handle())
+        };
+        let Some(head) = self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_end() == at && area.page_count() > 0)
+        else {
+            return false;
+        };
+        let Some(tail) = self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == at && area.page_count() > 0)
+        else {
+            return false;
+        };
+        let (head_area, tail_area) = (&self.areas[head], &self.areas[tail]);
+        if !head_area.can_merge(tail_area) || is_mirrored(head_area) || is_mirrored(tail_area) {
+            return false;
+        }
+        let tail_area = self.areas.remove(tail);
+        let head = if tail < head { head - 1 } else { head };
+        self.areas[head].merge(tail_area);
+        true
+    }
+
+    /// Apply the permission transitions the areas declared for `phase` by `with_transition`,
+    /// other harts must flush their tlb for them to take effect.
+    pub fn advance_phase(&mut self, phase: Phase) {
+        self.settle_faults();
+        for area in self.areas.iter_mut() {
+            area.advance_phase(&mut self.page_table, phase);
+        }
+    }
+
+    /// check the areas and the trampoline against `policy`, e.g. after exec or fork
+    pub fn audit(&self, policy: &VmPolicy) -> Vec<Violation> {
+        let overlaps = |area: &MapArea, range: VPNRange| {
+            area.vpn_range.get_start() < range.get_end()
+                && range.get_start() < area.vpn_range.get_end()
+        };
+        let mut violations = Vec::new();
+        for area in self.areas.iter() {
+            let start = area.vpn_range.get_start();
+            if policy.forbid_user_wx()
+                && area.allows_user()
+                && area.allows(AccessType::Write)
+                && area.allows(AccessType::Execute)
+            {
+                violations.push(Violation::UserWritableExecutable { start });
+            }
+            if policy
+                .stack()
+                .is_some_and(|stack| overlaps(area, stack) && area.allows(AccessType::Execute))
+            {
+                violations.push(Violation::ExecutableStack { start });
+            }
+            if policy.user_range().is_some_and(|user_range| {
+                area.map_type() == MapType::Identical && overlaps(area, user_range)
+            }) {
+                violations.push(Violation::IdenticalInUserRange { start });
+            }
+        }
+        if policy.kernel_only_trampoline() {
+            if let Some(vpn) = self.trampoline {
+                if pte_ext::translate(&self.page_table, vpn)
+                    .is_some_and(|pte| pte.is_valid() && pte.flags().contains(PTEFlags::U))
+                {
+                    violations.push(Violation::UserTrampoline { vpn });
+                }
+            }
+        }
+        violations
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let (colored_pages, color_misses) = self
+            .areas
+            .iter()
+            .map(|area| area.color_hits_and_misses(&self.frames))
+            .fold((0, 0), |(hits, misses), (h, m)| (hits + h, misses + m));
+        let mut origin_frames = [0; AreaOrigin::COUNT];
+        for area in self.areas.iter() {
+            if let Some(origin) = area.origin() {
+                origin_frames[origin as usize] += area.resident_pages(&self.frames);
+            }
+        }
+        let (mut cow_shared_pages, mut private_pages) = (0, 0);
+        for area in self.areas.iter() {
+            for (_, info) in self.frames.pages(area.vpn_range) {
+                match info.state() {
+                    _ if info.frame().is_none() => {}
+                    PageState::ResidentCow if info.is_shared() => cow_shared_pages += 1,
+                    _ if !info.is_shared() => private_pages += 1,
+                    _ => {}
+                }
+            }
+        }
+        MemoryStats {
+            areas: self.areas.len(),
+            mapped_pages: self.areas.iter().map(|area| area.page_count()).sum(),
+            data_frames: self
+                .areas
+                .iter()
+                .map(|area| area.resident_pages(&self.frames))
+                .sum::<usize>()
+                + self.pending_frames.len(),
+            committed_pages: self.committed_pages(),
+            page_table_frames: self.page_table_usage.frames(),
+            colored_pages,
+            color_misses,
+            fault_around_pages: self.fault_around.window(),
+            prefetched_pages: self.fault_around.prefetched_total(),
+            faults_avoided: self.fault_around.faults_avoided(&self.page_table),
+            prefetch_wasted: self.fault_around.wasted(),
+            origin_frames,
+            cow_shared_bytes: cow_shared_pages * PAGE_SIZE,
+            private_bytes: private_pages * PAGE_SIZE,
+            cow_breaks: self.cow_breaks,
+        }
+    }
+
+    /// frames backing the pages of the areas and the states of the pages
+    pub(crate) fn data_frames(&self) -> impl Iterator<Item = (&Arc<FrameTracker>, PageState)> {
+        self.areas.iter().flat_map(move |area| {
+            self.frames
+                .pages(area.vpn_range)
+                .filter_map(|(_, info)| info.frame().map(|frame| (frame, info.state())))
+        })
+    }
+
+    /// number of pages populated ahead of a fault in areas accessed sequentially
+    pub fn fault_around(&self) -> usize {
+        self.fault_around.window()
+    }
+
+    pub fn set_fault_around(&mut self, pages: usize) {
+        self.fault_around.set_window(pages);
+    }
+
+    /// copy-on-write areas of at most this many pages are copied eagerly on clone
+    pub fn eager_copy_threshold(&self) -> usize {
+        self.eager_copy_pages
+    }
+
+    pub fn set_eager_copy_threshold(&mut self, pages: usize) {
+        self.eager_copy_pages = pages;
+    }
+
+    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        pte_ext::translate(&self.page_table, vpn)
+    }
+
+    /// Translate `vpn`, telling pages never mapped from pages unmapped since,
+    /// which helps to diagnose use-after-unmap with the `poison-unmapped` feature.
+    pub fn translate_checked(&self, vpn: VirtPageNum) -> Translation {
+        match pte_ext::translate(&self.page_table, vpn) {
+            Some(pte) if pte.is_valid() => Translation::Mapped(pte),
+            Some(pte) if pte_ext::is_poisoned(&pte) => Translation::Unmapped,
+            _ => Translation::NotMapped,
+        }
+    }
+
+    /// Everything known about `va` in one place: the area covering it, the state and
+    /// backing of its page, and the flags of its pte, for debugging.
+    pub fn explain(&self, va: VirtAddr) -> AddressExplanation {
+        let vpn = va.floor();
+        let pte = pte_ext::translate(&self.page_table, vpn);
+        let mapped = pte.filter(|pte| pte.is_valid());
+        let area = self.areas.iter().find(|area| area.contains(vpn));
+        let state = self.frames.state(vpn);
+        let backing = match (area, mapped) {
+            (_, _) if state == PageState::SwappedOut => Backing::Swapped,
+            (Some(area), Some(pte)) if area.map_type() == MapType::Identical => {
+                Backing::Identical(pte.ppn())
+            }
+            (Some(area), Some(pte)) if area.map_type() == MapType::Mmio => Backing::Mmio(pte.ppn()),
+            (Some(_), _) => match self.frames.get(vpn) {
+                Some(info) => match info.frame() {
+                    Some(frame) => Backing::Frame {
+                        ppn: frame.ppn,
+                        shared: info.is_shared(),
+                    },
+                    None => Backing::None,
+                },
+                None => Backing::None,
+            },
+            (None, _) => Backing::None,
+        };
+        AddressExplanation {
+            va,
+            vpn,
+            area: area.map(|area| AreaSummary {
+                handle: area.handle(),
+                start: area.vpn_range.get_start(),
+                end: area.vpn_range.get_end(),
+                map_type: area.map_type(),
+                perm: area.permission(),
+                class: area.class(),
+                origin: area.origin(),
+                tag: area.tag().map(String::from),
+            }),
+            state,
+            pte_flags: mapped.map(|pte| pte.flags()),
+            software_bits: mapped.map_or(0, |pte| pte_ext::software_bits(&pte)),
+            backing,
+            trampoline: self.trampoline == Some(vpn),
+            poisoned: pte.is_some_and(|pte| !pte.is_valid() && pte_ext::is_poisoned(&pte)),
+        }
+    }
+
+    /// Reference to a `T` at `va` through the physical mapping of its page, e.g. the trap
+    /// context of a process. The page must be resident and writable, and the value must be
+    /// aligned and fit in the page.
+    ///
+    /// # Safety
+    ///
+    /// The bytes at `va` must be a valid `T`, e.g. written as a `T` before or any bit pattern
+    /// being valid for `T`, and the page must not be accessed through another reference, such
+    /// as a mapping in another memory set, while the returned one lives.
+    pub unsafe fn typed_page_mut<T>(&mut self, va: VirtAddr) -> Result<&mut T, PageAccessError> {
+        if va.0 & (core::mem::align_of::<T>() - 1) != 0 {
+            return Err(PageAccessError::Misaligned);
+        }
+        let offset = va.page_offset();
+        if offset + core::mem::size_of::<T>() > PAGE_SIZE {
+            return Err(PageAccessError::CrossesPage);
+        }
+        let pte = pte_ext::translate(&self.page_table, va.floor())
+            .filter(|pte| pte.is_valid())
+            .ok_or(PageAccessError::NotMapped)?;
+        if !pte.writable() {
+            return Err(PageAccessError::NotWritable);
+        }
+        let bytes = &mut pte.ppn().get_bytes_array()[offset..];
+        // the caller guarantees that the bytes are a valid `T`, alignment and size are checked
+        Ok(&mut *(bytes.as_mut_ptr() as *mut T))
+    }
+
+    /// Slices of the frames covering `len` bytes at `va`, one per page, so that kernel code can
+    /// access a user buffer straddling pages. Missing pages of lazy areas are populated and
+    /// copy-on-write pages of writable areas are copied first, so that writing through the
+    /// slices does not leak into other memory sets. Fails if part of the range is outside
+    /// of all areas or cannot be populated.
+    pub fn translated_byte_buffer(
+        &mut self,
+        va: VirtAddr,
+        len: usize,
+    ) -> Result<Vec<&mut [u8]>, PageAccessError> {
+        let end = va.0.checked_add(len).ok_or(PageAccessError::NotMapped)?;
+        let mut pieces = Vec::new();
+        let mut start = va.0;
+        while start < end {
+            let vpn = VirtAddr(start).floor();
+            let access = match self.areas.iter().find(|area| area.contains(vpn)) {
+                Some(area) if area.allows(AccessType::Write) => AccessType::Write,
+                Some(_) => AccessType::Read,
+                None => return Err(PageAccessError::NotMapped),
+            };
+            let needs_fault =
+                match pte_ext::translate(&self.page_table, vpn).filter(|pte| pte.is_valid()) {
+                    Some(pte) => access == AccessType::Write && !pte.writable(),
+                    None => true,
+                };
+            if needs_fault
+                && self.handle_page_fault(VirtAddr(start), access) != FaultResult::Resolved
+            {
+                return Err(PageAccessError::NotMapped);
+            }
+            let page_end = (VirtAddr::from(vpn).0 + PAGE_SIZE).min(end);
+            pieces.push((start - VirtAddr::from(vpn).0, page_end - start, vpn));
+            start = page_end;
+        }
+        Ok(pieces
+            .into_iter()
+            .map(|(offset, len, vpn)| {
+                let ppn = pte_ext::translate(&self.page_table, vpn).unwrap().ppn();
+                &mut ppn.get_bytes_array()[offset..offset + len]
+            })
+            .collect())
+    }
+
+    /// Copy `dst.len()` bytes of user memory at `src_va` into `dst`. Every page must lie in a
+    /// user area readable by the user, missing pages of lazy areas are populated. Nothing is
+    /// copied on failure.
+    pub fn copy_from_user(
+        &mut self,
+        dst: &mut [u8],
+        src_va: VirtAddr,
+    ) -> Result<(), PageAccessError> {
+        let mut copied = 0;
+        for (ppn, offset, len) in self.user_pages(src_va, dst.len(), AccessType::Read)? {
+            dst[copied..copied + len].copy_from_slice(&ppn.get_bytes_array()[offset..offset + len]);
+            copied += len;
+        }
+        Ok(())
+    }
+
+    /// Copy `src` into user memory at `dst_va`. Every page must lie in a user area writable
+    /// by the user, missing pages are populated and copy-on-write pages copied first.
+    /// Nothing is copied on failure.
+    pub fn copy_to_user(&mut self, dst_va: VirtAddr, src: &[u8]) -> Result<(), PageAccessError> {
+        let mut copied = 0;
+        for (ppn, offset, len) in self.user_pages(dst_va, src.len(), AccessType::Write)? {
+            ppn.get_bytes_array()[offset..offset + len].copy_from_slice(&src[copied..copied + len]);
+            copied += len;
+        }
+        Ok(())
+    }
+
+    /// Check that every page of `len` bytes at `va` lies in a user area allowing the user
+    /// `required`, e.g. to reject a bad pointer passed to a syscall before touching memory.
+    /// Pages of lazy areas not populated yet pass, they are populated on access.
+    pub fn check_access(
+        &self,
+        va: VirtAddr,
+        len: usize,
+        required: MapPermission,
+    ) -> Result<(), PageAccessError> {
+        let end = va.0.checked_add(len).ok_or(PageAccessError::NotMapped)?;
+        let mut vpn = va.floor();
+        while vpn < VirtAddr(end).ceil() {
+            let area = self
+                .areas
+                .iter()
+                .find(|area| area.contains(vpn))
+                .ok_or(PageAccessError::NotMapped)?;
+            if area.class() != AreaClass::User || !area.allows_user() {
+                return Err(PageAccessError::NotUser);
+            }
+            for (perm, access, err) in [
+                (
+                    MapPermission::R,
+                    AccessType::Read,
+                    PageAccessError::NotReadable,
+                ),
+                (
+                    MapPermission::W,
+                    AccessType::Write,
+                    PageAccessError::NotWritable,
+                ),
+                (
+                    MapPermission::X,
+                    AccessType::Execute,
+                    PageAccessError::NotExecutable,
+                ),
+            ] {
+                if required.contains(perm) && !area.allows(access) {
+                    return Err(err);
+                }
+            }
+            // the rest of the area passes as well
+            vpn = area.vpn_range.get_end();
+        }
+        Ok(())
+    }
+
+    /// frames, offsets and lengths covering `len` bytes at `va`, once every page is checked
+    /// and made ready for `access` by the user
+    fn user_pages(
+        &mut self,
+        va: VirtAddr,
+        len: usize,
+        access: AccessType,
+    ) -> Result<Vec<(PhysPageNum, usize, usize)>, PageAccessError> {
+        let end = va.0.checked_add(len).ok_or(PageAccessError::NotMapped)?;
+        let mut pieces = Vec::new();
+        let mut start = va.0;
+        while start < end {
+            let vpn = VirtAddr(start).floor();
+            let area = self
+                .areas
+                .iter()
+                .find(|area| area.contains(vpn))
+                .ok_or(PageAccessError::NotMapped)?;
+            if area.class() != AreaClass::User || !area.allows_user() {
+                return Err(PageAccessError::NotUser);
+            }
+            if !area.allows(access) {
+                return Err(match access {
+                    AccessType::Write => PageAccessError::NotWritable,
+                    _ => PageAccessError::NotReadable,
+                });
+            }
+            let ready = |pte: &PageTableEntry| {
+                let flags = pte.flags();
+                pte.is_valid()
+                    && flags.contains(PTEFlags::U)
+                    && match access {
+                        AccessType::Write => flags.contains(PTEFlags::W),
+                        _ => flags.contains(PTEFlags::R),
+                    }
+            };
+            if !pte_ext::translate(&self.page_table, vpn).is_some_and(|pte| ready(&pte))
+                && self.handle_page_fault(VirtAddr(start), access) != FaultResult::Resolved
+            {
+                return Err(PageAccessError::NotMapped);
+            }
+            let pte = pte_ext::translate(&self.page_table, vpn)
+                .filter(ready)
+                .ok_or(PageAccessError::NotMapped)?;
+            let page_start = VirtAddr::from(vpn).0;
+            let page_end = (page_start + PAGE_SIZE).min(end);
+            pieces.push((pte.ppn(), start - page_start, page_end - start));
+            start = page_end;
+        }
+        Ok(pieces)
+    }
+
+    /// panics unless the memory at `va` holds `expected`
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn assert_range_eq(&self, va: VirtAddr, expected: &[u8]) {
+        let mut checked = 0;
+        while checked < expected.len() {
+            let current = VirtAddr(va.0 + checked);
+            let pte = pte_ext::translate(&self.page_table, current.floor())
+                .filter(|pte| pte.is_valid())
+                .unwrap_or_else(|| panic!("{:?} is not mapped", current));
+            let offset = current.page_offset();
+            let len = (PAGE_SIZE - offset).min(expected.len() - checked);
+            let actual = &pte.ppn().get_bytes_array()[offset..offset + len];
+            if let Some(idx) = (0..len).find(|idx| actual[*idx] != expected[checked + idx]) {
+                panic!(
+                    "{:?} holds {:#x}, expected {:#x}",
+                    VirtAddr(current.0 + idx),
+                    actual[idx],
+                    expected[checked + idx]
+                );
+            }
+            checked += len;
+        }
+    }
+
+    /// Zero `len` bytes at `va` through the frames of the pages. Whole pages of lazy areas
+    /// lose their frame instead and are populated with zeros on the next access, other harts
+    /// must flush their tlb for that. Frames mapped elsewhere as well are copied first. Every
+    /// page must lie in a framed or lazy user area writable by the user, otherwise nothing is
+    /// zeroed. Swapped-out pages are dropped from swap if wholly zeroed, swapped in otherwise.
+    /// Running out of frames for a copy or a swap-in stops at that page with `OutOfMemory`.
+    pub fn zero_range(&mut self, va: VirtAddr, len: usize) -> Result<(), PageAccessError> {
+        self.settle_faults();
+        let end_va = VirtAddr(va.0.checked_add(len).ok_or(PageAccessError::NotMapped)?);
+        let range = VPNRange::new(va.floor(), end_va.ceil());
+        for vpn in range {
+            let area = self
+                .areas
+                .iter()
+                .find(|area| area.contains(vpn))
+                .ok_or(PageAccessError::NotMapped)?;
+            if area.class() != AreaClass::User || !area.allows_user() {
+                return Err(PageAccessError::NotUser);
+            }
+            if !area.allows(AccessType::Write) {
+                return Err(PageAccessError::NotWritable);
+            }
+            if !matches!(area.map_type(), MapType::Framed | MapType::Lazy) {
+                return Err(PageAccessError::NotPrivate);
+            }
+        }
+
+        for vpn in range {
+            let area = self
+                .areas
+                .iter_mut()
+                .find(|area| area.contains(vpn))
+                .unwrap();
+            let page_start = VirtAddr::from(vpn).0;
+            let start = va.0.max(page_start) - page_start;
+            let end = end_va.0.min(page_start + PAGE_SIZE) - page_start;
+            let whole_lazy = area.map_type() == MapType::Lazy && start == 0 && end == PAGE_SIZE;
+            if self.frames.state(vpn) == PageState::SwappedOut {
+                if whole_lazy {
+                    // dropping the page frees its swap slot
+                    area.unmap_one(&mut self.page_table, &mut self.frames, vpn);
+                    continue;
+                }
+                // the rest of the page comes back from its swap slot
+                if !Self::swap_in_page(&mut self.page_table, &mut self.frames, area, vpn) {
+                    return Err(PageAccessError::OutOfMemory);
+                }
+            }
+            if !pte_ext::translate(&self.page_table, vpn).is_some_and(|pte| pte.is_valid()) {
+                // not populated yet, reads as zeros already
+                continue;
+            }
+            if whole_lazy {
+                self.fault_around
+                    .retire(&self.page_table, VPNRange::new(vpn, VirtPageNum(vpn.0 + 1)));
+                area.unmap_one(&mut self.page_table, &mut self.frames, vpn);
+                continue;
+            }
+            let cow = area.needs_copy(&self.frames, vpn);
+            if !area.unshare(&mut self.page_table, &mut self.frames, vpn) {
+                return Err(PageAccessError::OutOfMemory);
+            }
+            if cow {
+                self.cow_breaks += 1;
+            }
+            let ppn = pte_ext::translate(&self.page_table, vpn).unwrap().ppn();
+            ppn.get_bytes_array()[start..end].fill(0);
+            if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+                soft_dirty.insert(vpn);
+            }
+        }
+        Ok(())
+    }
+
+    /// keep the symbols of the loaded program to annotate faults, see `symbolize`
+    #[cfg(feature = "symbols")]
+    pub fn attach_symbols(&mut self, symbols: crate::elf::SymbolIndex) {
+        self.symbols = Some(symbols);
+    }
+
+    /// function containing `pc` and the offset of `pc` in it, if symbols are attached
+    #[cfg(feature = "symbols")]
+    pub fn symbolize(&self, pc: usize) -> Option<(&str, usize)> {
+        self.symbols.as_ref()?.lookup(pc)
+    }
+
+    /// bottom and top of the stack area holding `sp`, a full stack has `sp` at its bottom
+    /// and an empty one at its top
+    pub fn stack_bounds_for(&self, sp: VirtAddr) -> Option<(VirtAddr, VirtAddr)> {
+        self.areas
+            .iter()
+            .filter(|area| area.is_stack())
+            .map(|area| {
+                let bottom: VirtAddr = area.vpn_range.get_start().into();
+                let top: VirtAddr = area.vpn_range.get_end().into();
+                (bottom, top)
+            })
+            .find(|(bottom, top)| *bottom <= sp && sp <= *top)
+    }
+
+    /// Rsw bits of the pte of `vpn`, those of its area if the page is not mapped yet.
+    /// Returns `None` if no area contains the page.
+    pub fn software_bits(&self, vpn: VirtPageNum) -> Option<u8> {
+        match pte_ext::translate(&self.page_table, vpn).filter(|pte| pte.is_valid()) {
+            Some(pte) => Some(pte_ext::software_bits(&pte)),
+            None => self
+                .areas
+                .iter()
+                .find(|area| area.contains(vpn))
+                .map(|area| area.software_bits()),
+        }
+    }
+
+    /// Override the rsw bits of a mapped page, they are kept until the page is unmapped.
+    /// Returns false if the page is not mapped.
+    pub fn set_software_bits(&mut self, vpn: VirtPageNum, bits: u8) -> bool {
+        pte_ext::set_software_bits(&self.page_table, vpn, bits)
+    }
+
+    /// Read and clear the accessed and dirty bits of the page `vpn`, `None` if it is not
+    /// mapped. Other harts must flush their tlb before the bits are set again, harts that
+    /// fault instead of setting them are not supported.
+    pub fn take_accessed_dirty(&mut self, vpn: VirtPageNum) -> Option<(bool, bool)> {
+        pte_ext::take_accessed_dirty(&self.page_table, vpn, PTEFlags::A | PTEFlags::D)
+            .map(|taken| (taken.contains(PTEFlags::A), taken.contains(PTEFlags::D)))
+    }
+
+    /// pages of the area containing `va` accessed since the last call, their bits are cleared,
+    /// see `take_accessed_dirty`
+    pub fn take_accessed(&mut self, va: VirtAddr) -> Vec<VirtPageNum> {
+        self.take_area_bits(va, PTEFlags::A)
+    }
+
+    /// pages of the area containing `va` written since the last call, their bits are cleared,
+    /// see `take_accessed_dirty`
+    pub fn take_dirty(&mut self, va: VirtAddr) -> Vec<VirtPageNum> {
+        self.take_area_bits(va, PTEFlags::D)
+    }
+
+    fn take_area_bits(&mut self, va: VirtAddr, flags: PTEFlags) -> Vec<VirtPageNum> {
+        let Some(area) = self.find_area(va) else {
+            return Vec::new();
+        };
+        area.vpn_range
+            .into_iter()
+            .filter(|vpn| {
+                pte_ext::take_accessed_dirty(&self.page_table, *vpn, flags)
+                    .is_some_and(|taken| !taken.is_empty())
+            })
+            .collect()
+    }
+
+    /// shrink the area containing `start` to end at `new_end`
+    pub fn shrink_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        if let Some(idx) = self.area_index(start.floor()) {
+            let area = &mut self.areas[idx];
+            self.fault_around.retire(
+                &self.page_table,
+                VPNRange::new(new_end.ceil(), area.vpn_range.get_end()),
+            );
+            area.shrink_to(&mut self.page_table, &mut self.frames, new_end.ceil());
+            self.debug_assert_invariants();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// extend the area containing `start` to end at `new_end`
+    pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        if let Some(idx) = self.area_index(start.floor()) {
+            let area = &mut self.areas[idx];
+            let old_end = area.vpn_range.get_end();
+            area.append_to(&mut self.page_table, &mut self.frames, new_end.ceil());
+            self.page_table_usage
+                .record_range(VPNRange::new(old_end, new_end.ceil()));
+            if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+                soft_dirty.extend(VPNRange::new(old_end, new_end.ceil()));
+            }
+            self.debug_assert_invariants();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// clone the memory set, each area is cloned according to its `CloneStrategy`;
+    /// areas cloned copy-on-write are write-protected in `user_space` as well,
+    /// so other harts running it must flush their tlb, those that ran it before do on their
+    /// next `activate`. `None` if frames ran out swapping in its evicted pages
+    pub fn from_existed_user(
+        user_space: &mut Self,
+        trampline_start_va: usize,
+        trampline_start_pa: usize,
+    ) -> Option<Self> {
+        Self::clone_areas(user_space, trampline_start_va, trampline_start_pa, false)
+    }
+
+    /// Clone the memory set copy-on-write: frames of areas that would be copied eagerly
+    /// are shared read-only by both memory sets and copied on the first write instead, so
+    /// that fork does not depend on the memory size. The trampoline is mapped where it is
+    /// mapped in `parent`, other harts running it must flush their tlb, those that ran it
+    /// before do on their next `activate`. `None` if frames ran out swapping in the evicted
+    /// pages of `parent`.
+    pub fn clone_cow(parent: &mut Self) -> Option<Self> {
+        let vpn = parent
+            .trampoline
+            .expect("no trampoline to map into the clone");
+        let ppn = pte_ext::translate(&parent.page_table, vpn).unwrap().ppn();
+        let trampoline_va: VirtAddr = vpn.into();
+        let trampoline_pa: PhysAddr = ppn.into();
+        Self::clone_areas(parent, trampoline_va.0, trampoline_pa.0, true)
+    }
+
+    fn clone_areas(
+        user_space: &mut Self,
+        trampline_start_va: usize,
+        trampline_start_pa: usize,
+        cow: bool,
+    ) -> Option<Self> {
+        user_space.settle_faults();
+        // the clone copies or shares frames, evicted pages have none
+        if !user_space.swap_in_all() {
+            return None;
+        }
+        let mut memory_set = Self::new_bare();
+        memory_set.eager_copy_pages = user_space.eager_copy_pages;
+        memory_set.heap = user_space.heap;
+        memory_set.program_headers = user_space.program_headers;
+        memory_set.swap_backend = user_space.swap_backend.clone();
+        memory_set.mmap_buddy = user_space.mmap_buddy.clone();
+        memory_set.forbid_wx = user_space.forbid_wx;
+
+        memory_set.map_trampoline(
+            VirtAddr::from(trampline_start_va).into(),
+            PhysAddr::from(trampline_start_pa).into(),
+        );
+        for mapping in user_space.fixed_mappings.iter() {
+            memory_set.map_fixed(mapping.name, mapping.vpn, mapping.ppn);
+        }
+
+        // copy data sections/trap_context/user_stack
+        for area in user_space
+            .areas
+            .iter_mut()
+            .filter(|area| area.class() == AreaClass::User)
+        {
+            let mut new_area = MapArea::from_another(area);
+            let clone_strategy = match area.clone_strategy() {
+                // breaking copy-on-write of tiny areas costs more faults than copying
+                CloneStrategy::CoW if area.page_count() <= user_space.eager_copy_pages => {
+                    CloneStrategy::EagerCopy
+                }
+                CloneStrategy::EagerCopy
+                    if cow && area.page_count() > user_space.eager_copy_pages =>
+                {
+                    CloneStrategy::CoW
+                }
+                // the frames of shared areas are never copied
+                clone_strategy
+                    if area.map_type() == MapType::Shared
+                        && clone_strategy != CloneStrategy::Skip =>
+                {
+                    CloneStrategy::Share
+                }
+                clone_strategy => clone_strategy,
+            };
+            match clone_strategy {
+                // the same physical memory, possibly mmio, mapping it again is all it takes
+                _ if area.map_type().is_direct() && clone_strategy != CloneStrategy::Skip => {
+                    memory_set.push(new_area, None)
+                }
+                CloneStrategy::EagerCopy => {
+                    memory_set.push(new_area, None);
+                    let new_area = memory_set.areas.last_mut().unwrap();
+                    // copy data from another space, pages of lazy areas only if accessed
+                    for vpn in area.vpn_range {
+                        let Some(src_pte) = pte_ext::translate(&user_space.page_table, vpn)
+                            .filter(|pte| pte.is_valid())
+                        else {
+                            continue;
+                        };
+                        if new_area.map_type() == MapType::Lazy {
+                            new_area.map_one(
+                                &mut memory_set.page_table,
+                                &mut memory_set.frames,
+                                vpn,
+                            );
+                        }
+                        let dst_ppn = pte_ext::translate(&memory_set.page_table, vpn)
+                            .unwrap()
+                            .ppn();
+                        dst_ppn
+                            .get_bytes_array()
+                            .copy_from_slice(src_pte.ppn().get_bytes_array());
+                    }
+                }
+                CloneStrategy::CoW => {
+                    area.write_protect(&mut user_space.page_table);
+                    area.mark_cow(&mut user_space.frames);
+                    new_area.share_frames(
+                        &mut memory_set.page_table,
+                        &mut memory_set.frames,
+                        area,
+                        &user_space.frames,
+                        true,
+                    );
+                    memory_set.push_mapped(new_area);
+                }
+                CloneStrategy::Share => {
+                    new_area.share_frames(
+                        &mut memory_set.page_table,
+                        &mut memory_set.frames,
+                        area,
+                        &user_space.frames,
+                        false,
+                    );
+                    memory_set.push_mapped(new_area);
+                }
+                CloneStrategy::Zero => memory_set.push(new_area, None),
+                CloneStrategy::Skip => {}
+            }
+        }
+
+        Some(memory_set)
+    }
+
+    /// Check the invariants of the memory set in debug builds, panicking at the first broken:
+    /// areas are disjoint, pages mapped in framed and lazy areas are tracked by the frame
+    /// table and tracked frames are mapped, ptes grant no more than the permission of their
+    /// area, the trampoline is mapped once set and in every loaded program, and so are the
+    /// fixed mappings. Invoked after the mutating operations, so that a bug surfaces where it
+    /// broke the memory set.
+    pub fn debug_assert_invariants(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let mut ranges = self
+            .areas
+            .iter()
+            .filter(|area| area.page_count() > 0)
+            .map(|area| (area.vpn_range.get_start(), area.vpn_range.get_end()))
+            .collect::<Vec<_>>();
+        ranges.sort();
+        for pair in ranges.windows(2) {
+            assert!(
+                pair[0].1 <= pair[1].0,
+                "areas overlap at {:#x}",
+                pair[1].0 .0
+            );
+        }
+        let pending = |vpn: VirtPageNum| self.pending_frames.iter().any(|(page, _)| *page == vpn);
+        for area in self.areas.iter() {
+            for vpn in area.vpn_range {
+                let Some(pte) =
+                    pte_ext::translate(&self.page_table, vpn).filter(|pte| pte.is_valid())
+                else {
+                    continue;
+                };
+                let granted = pte.flags().bits() & !area.permission().bits();
+                assert!(
+                    granted & (MapPermission::all().bits()) == 0,
+                    "pte of {:#x} grants more than its area",
+                    vpn.0
+                );
+                if !area.map_type().is_direct() && !pending(vpn) {
+                    assert!(
+                        self.frames
+                            .get(vpn)
+                            .and_then(|info| info.frame())
+                            .is_some_and(|frame| frame.ppn == pte.ppn()),
+                        "mapped page {:#x} has no tracked frame",
+                        vpn.0
+                    );
+                }
+            }
+            if area.map_type() != MapType::Lazy {
+                for (vpn, info) in self.frames.pages(area.vpn_range) {
+                    assert!(
+                        info.frame().is_none()
+                            || pte_ext::translate(&self.page_table, vpn)
+                                .is_some_and(|pte| pte.is_valid()),
+                        "tracked frame of {:#x} is not mapped",
+                        vpn.0
+                    );
+                }
+            }
+        }
+        if let Some(vpn) = self.trampoline {
+            assert!(
+                pte_ext::translate(&self.page_table, vpn).is_some_and(|pte| pte.is_valid()),
+                "trampoline is not mapped"
+            );
+        } else {
+            assert!(
+                !self
+                    .areas
+                    .iter()
+                    .any(|area| area.origin() == Some(AreaOrigin::Exec)),
+                "program loaded without a trampoline"
+            );
+        }
+        for mapping in self.fixed_mappings.iter() {
+            assert!(
+                pte_ext::translate(&self.page_table, mapping.vpn)
+                    .is_some_and(|pte| pte.is_valid() && pte.ppn() == mapping.ppn),
+                "fixed mapping {} is not mapped",
+                mapping.name
+            );
+        }
+    }
+
+    /// whether `map_area` may be given `perm`: kernel areas and device memory never become
+    /// user-accessible
+    fn may_protect(map_area: &MapArea, perm: MapPermission) -> bool {
+        !perm.contains(MapPermission::U)
+            || (map_area.class() == AreaClass::User && map_area.map_type() != MapType::Mmio)
+    }
+
+    /// kernel areas must not be user-accessible
+    fn check_class(map_area: &MapArea) {
+        assert!(
+            map_area.class() == AreaClass::User || !map_area.allows_user(),
+            "kernel area is user-accessible"
+        );
+    }
+
+    /// Reject mappings that are both writable and executable: `push` panics on them, the
+    /// transactions fail with `TransactionError::WritableExecutable`, mmaps and protects
+    /// fail, and so does loading an elf with such a segment by `replace_from_elf`. Clones and
+    /// replaced images keep the setting, existing areas are not checked, see `audit`.
+    pub fn set_forbid_wx(&mut self, forbid_wx: bool) {
+        self.forbid_wx = forbid_wx;
+    }
+
+    pub fn forbids_wx(&self) -> bool {
+        self.forbid_wx
+    }
+
+    fn is_wx_forbidden(&self, perm: MapPermission) -> bool {
+        self.forbid_wx && perm.contains(MapPermission::W | MapPermission::X)
+    }
+
+    fn check_wx(&self, map_area: &MapArea) {
+        assert!(
+            !self.is_wx_forbidden(map_area.permission()),
+            "area is writable and executable"
+        );
+    }
+
+    /// the error `push` would panic with on `map_area`, see `check_class` and `check_wx`
+    fn validate_push(&self, map_area: &MapArea) -> Result<(), TransactionError> {
+        if map_area.class() != AreaClass::User && map_area.allows_user() {
+            Err(TransactionError::UserKernelArea)
+        } else if self.is_wx_forbidden(map_area.permission()) {
+            Err(TransactionError::WritableExecutable)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// push an area whose pages are mapped already
+    fn push_mapped(&mut self, map_area: MapArea) {
+        self.page_table_usage.record_range(map_area.vpn_range);
+        self.areas.push(map_area);
+        self.debug_assert_invariants();
+    }
+
+    /// Register the callback invoked on the first write to a page of an area
+    /// created `with_write_notify`, before write permission is restored.
+    pub fn set_write_notifier(&mut self, notifier: Box<dyn FnMut(VirtPageNum) + Send>) {
+        self.write_notifier = Some(notifier);
+    }
+
+    /// Start tracking written pages in software, like linux soft-dirty bits.
+    /// All writable pages are write-protected, newly mapped pages count as dirty.
+    pub fn enable_soft_dirty(&mut self) {
+        if self.soft_dirty.is_none() {
+            self.clear_soft_dirty();
+        }
+    }
+
+    /// Forget the pages written so far and write-protect them again,
+    /// other harts must flush their tlb for the protection to take effect.
+    pub fn clear_soft_dirty(&mut self) {
+        self.settle_faults();
+        for area in self.areas.iter_mut() {
+            if area.allows(AccessType::Write) {
+                area.write_protect(&mut self.page_table);
+            }
+        }
+        self.soft_dirty = Some(BTreeSet::new());
+    }
+
+    /// pages written since soft-dirty tracking was enabled or last cleared
+    pub fn soft_dirty_pages(&self) -> Vec<VirtPageNum> {
+        match self.soft_dirty.as_ref() {
+            Some(soft_dirty) => soft_dirty
+                .iter()
+                .chain(self.pending_dirty.iter())
+                .copied()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .filter(|vpn| self.areas.iter().any(|area| area.contains(*vpn)))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Resolve a page fault raised by an access to `va`: pages of lazy areas are
+    /// populated on first access, writes to copy-on-write, write-notified and
+    /// soft-dirty tracked pages restore write permission. Faults just below an area
+    /// growing down extend it first, see `MapArea::with_grows_down`.
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: AccessType) -> FaultResult {
+        let result = self.resolve_page_fault(va, access);
+        self.debug_assert_invariants();
+        result
+    }
+
+    fn resolve_page_fault(&mut self, va: VirtAddr, access: AccessType) -> FaultResult {
+        self.settle_faults();
+        let vpn = va.floor();
+        if !self.areas.iter().any(|area| area.contains(vpn)) && !self.grow_down_to(vpn) {
+            return FaultResult::NotMapped;
+        }
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.contains(vpn))
+            .unwrap();
+        if !area.allows(access) {
+            return FaultResult::PermissionDenied;
+        }
+
+        let Some(pte) = pte_ext::translate(&self.page_table, vpn).filter(|pte| pte.is_valid())
+        else {
+            if self.frames.state(vpn) == PageState::SwappedOut {
+                if !Self::swap_in_page(&mut self.page_table, &mut self.frames, area, vpn)
+                    && !(Self::run_reclaim_callback(&mut self.reclaim_callback)
+                        && Self::swap_in_page(&mut self.page_table, &mut self.frames, area, vpn))
+                {
+                    return FaultResult::OutOfMemory;
+                }
+                return FaultResult::Resolved;
+            }
+            if area.map_type() != MapType::Lazy {
+                return FaultResult::NotMapped;
+            }
+            if let Some(token) = Self::userfault_token(&self.userfault_regions, vpn) {
+                return FaultResult::Userfault {
+                    token,
+                    va,
+                    kind: access,
+                };
+            }
+            if access != AccessType::Write
+                && area.zero_page()
+                && area.map_zero_page(&mut self.page_table, &mut self.frames, vpn)
+            {
+                self.page_table_usage.record(vpn);
+                return FaultResult::Resolved;
+            }
+            let mut populated = area.fault_in(
+                &mut self.page_table,
+                &mut self.frames,
+                vpn,
+                &mut self.fault_around,
+                self.zeroed_pool.as_mut(),
+            );
+            if populated.is_none() && Self::run_reclaim_callback(&mut self.reclaim_callback) {
+                populated = area.fault_in(
+                    &mut self.page_table,
+                    &mut self.frames,
+                    vpn,
+                    &mut self.fault_around,
+                    self.zeroed_pool.as_mut(),
+                );
+            }
+            let Some(populated) = populated else {
+                return FaultResult::OutOfMemory;
+            };
+            self.page_table_usage.record_range(populated);
+            if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+                soft_dirty.extend(populated);
+            }
+            return FaultResult::Resolved;
+        };
+        // stale tlb entry, or another hart resolved the fault already
+        if access != AccessType::Write || pte.writable() {
+            return FaultResult::Resolved;
+        }
+
+        if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+            soft_dirty.insert(vpn);
+        }
+        if area.write_notify() {
+            if let Some(notifier) = self.write_notifier.as_mut() {
+                notifier(vpn);
+            }
+        }
+        let needs_copy = area.needs_copy(&self.frames, vpn);
+        if !area.resolve_write(&mut self.page_table, &mut self.frames, vpn)
+            && !(Self::run_reclaim_callback(&mut self.reclaim_callback)
+                && area.resolve_write(&mut self.page_table, &mut self.frames, vpn))
+        {
+            return FaultResult::OutOfMemory;
+        }
+        if needs_copy {
+            self.cow_breaks += 1;
+        }
+        FaultResult::Resolved
+    }
+
+    /// grow an area flagged by `MapArea::with_grows_down` down to `vpn`, returns false if
+    /// none may, the grown part would overlap another area or cannot be committed
+    fn grow_down_to(&mut self, vpn: VirtPageNum) -> bool {
+        let Some(idx) = self
+            .areas
+            .iter()
+            .position(|area| area.may_grow_down_to(vpn))
+        else {
+            return false;
+        };
+        let start = self.areas[idx].vpn_range.get_start();
+        let perm = if self.areas[idx].allows(AccessType::Write) {
+            MapPermission::W
+        } else {
+            MapPermission::empty()
+        };
+        if self.overlaps_any(vpn, start) || !self.may_commit(start.0 - vpn.0, perm) {
+            return false;
+        }
+        self.areas[idx].prepend_to(&mut self.page_table, &mut self.frames, vpn);
+        self.page_table_usage
+            .record_range(VPNRange::new(vpn, start));
+        if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+            soft_dirty.extend(VPNRange::new(vpn, start));
+        }
+        true
+    }
+
+    /// Register `callback` to free frames when a fault runs out of them, e.g. by evicting
+    /// pages or trimming pools. It returns whether anything was freed, the fault is retried
+    /// once then before `FaultResult::OutOfMemory` is returned.
+    pub fn set_reclaim_callback(&mut self, callback: Box<dyn FnMut() -> bool + Send>) {
+        self.reclaim_callback = Some(callback);
+    }
+
+    /// Lazy pages are populated with frames of `pool` first, `None` disables it and frees
+    /// its frames.
+    pub fn set_zeroed_pool(&mut self, pool: Option<ZeroedPool>) {
+        self.zeroed_pool = pool;
+    }
+
+    pub fn zeroed_pool(&self) -> Option<&ZeroedPool> {
+        self.zeroed_pool.as_ref()
+    }
+
+    /// add at most `budget` frames to the zeroed pool, returns how many were added
+    pub fn refill_zeroed_pool(&mut self, budget: usize) -> usize {
+        self.zeroed_pool
+            .as_mut()
+            .map_or(0, |pool| pool.fill(budget))
+    }
+
+    /// Pages are evicted to `backend` by `evict`, the backend is shared with clones.
+    /// Pages evicted to a previous backend stay there until swapped in.
+    pub fn set_swap_backend<B: SwapBackend + 'static>(&mut self, backend: B) {
+        self.swap_backend = Some(Arc::new(Mutex::new(backend)));
+    }
+
+    /// Write the page `vpn` to the swap backend and free its frame, the pte is left invalid
+    /// with the swapped marker and the next access faults it back in, see `swap_in`.
+    /// Only private resident pages of user-accessible framed or lazy areas are evicted,
+    /// returns false otherwise, without a backend or if the backend is full.
+    pub fn evict(&mut self, vpn: VirtPageNum) -> bool {
+        let Some(backend) = self.swap_backend.clone() else {
+            return false;
+        };
+        self.settle_faults();
+        let Some(area) = self.areas.iter().find(|area| area.contains(vpn)) else {
+            return false;
+        };
+        if !matches!(area.map_type(), MapType::Framed | MapType::Lazy) || !area.allows_user() {
+            return false;
+        }
+        if !self
+            .frames
+            .get(vpn)
+            .is_some_and(|info| info.state() == PageState::Resident && !info.is_shared())
+        {
+            return false;
+        }
+        let Some(pte) = pte_ext::translate(&self.page_table, vpn).filter(|pte| pte.is_valid())
+        else {
+            return false;
+        };
+        let Some(slot) = backend.lock().write_page(pte.ppn().get_bytes_array()) else {
+            return false;
+        };
+
+        pte_ext::unmap(&mut self.page_table, vpn);
+        pte_ext::mark_swapped(&mut self.page_table, vpn);
+        self.frames.swap_out(vpn, SwapSlot::new(backend, slot));
+        self.debug_assert_invariants();
+        true
+    }
+
+    /// read the evicted page `vpn` back, returns false if it is not swapped out or frames ran out
+    pub fn swap_in(&mut self, vpn: VirtPageNum) -> bool {
+        let Some(area) = self.areas.iter().find(|area| area.contains(vpn)) else {
+            return false;
+        };
+        if !Self::swap_in_page(&mut self.page_table, &mut self.frames, area, vpn) {
+            return false;
+        }
+        self.debug_assert_invariants();
+        true
+    }
+
+    /// swap in all evicted pages, returns false if frames ran out
+    fn swap_in_all(&mut self) -> bool {
+        let swapped = self
+            .areas
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, area)| {
+                self.frames
+                    .pages(area.vpn_range)
+                    .filter(|(_, info)| info.state() == PageState::SwappedOut)
+                    .map(move |(vpn, _)| (idx, vpn))
+            })
+            .collect::<Vec<_>>();
+        swapped.into_iter().all(|(idx, vpn)| {
+            Self::swap_in_page(
+                &mut self.page_table,
+                &mut self.frames,
+                &self.areas[idx],
+                vpn,
+            )
+        })
+    }
+
+    fn swap_in_page(
+        page_table: &mut PageTable,
+        frames: &mut FrameTable,
+        area: &MapArea,
+        vpn: VirtPageNum,
+    ) -> bool {
+        let Some(slot) = frames.get(vpn).and_then(|info| info.swap_slot()) else {
+            return false;
+        };
+        let Some(frame) = frame_alloc() else {
+            return false;
+        };
+        slot.read(frame.ppn.get_bytes_array());
+        let ppn = frame.ppn;
+        frames.swap_in(vpn, Arc::new(frame));
+        area.map_page(page_table, vpn, ppn, area.initial_pte_flags());
+        true
+    }
+
+    /// run the reclaim callback, if any, returns whether it freed frames
+    fn run_reclaim_callback(callback: &mut Option<Box<dyn FnMut() -> bool + Send>>) -> bool {
+        callback.as_mut().is_some_and(|callback| callback())
+    }
+
+    /// `handle_page_fault` under `policy`, with `AllocPolicy::NoBlock` this is
+    /// `handle_page_fault_nonalloc` and `FaultResult::WouldBlock` stands in for `NeedsAllocation`
+    pub fn try_handle_page_fault(
+        &mut self,
+        va: VirtAddr,
+        access: AccessType,
+        policy: AllocPolicy,
+    ) -> FaultResult {
+        match policy {
+            AllocPolicy::Block => self.handle_page_fault(va, access),
+            AllocPolicy::NoBlock(scratch) => {
+                match self.handle_page_fault_nonalloc(va, access, scratch) {
+                    FaultResult::NeedsAllocation => FaultResult::WouldBlock,
+                    result => result,
+                }
+            }
+        }
+    }
+
+    /// Resolve a page fault like `handle_page_fault` but without allocating heap memory,
+    /// so that it can run in the trap handler even if the kernel heap itself faults.
+    /// Frames are taken from `scratch`, bookkeeping goes to the slots reserved by
+    /// `reserve_fault_slots`. If either runs out, `FaultResult::NeedsAllocation` is
+    /// returned and the fault has to be resolved by `handle_page_fault` from schedulable
+    /// context. Fault-around is skipped, page table nodes are still allocated when missing,
+    /// and the write notifier must not allocate either.
+    pub fn handle_page_fault_nonalloc(
+        &mut self,
+        va: VirtAddr,
+        access: AccessType,
+        scratch: &mut FaultScratch,
+    ) -> FaultResult {
+        let vpn = va.floor();
+        let Some(area) = self.areas.iter_mut().find(|area| area.contains(vpn)) else {
+            // growing an area down needs allocations
+            if self.areas.iter().any(|area| area.may_grow_down_to(vpn)) {
+                return FaultResult::NeedsAllocation;
+            }
+            return FaultResult::NotMapped;
+        };
+        if !area.allows(access) {
+            return FaultResult::PermissionDenied;
+        }
+        let dirty_slot_needed = self.soft_dirty.is_some();
+        if dirty_slot_needed && self.pending_dirty.len() == self.pending_dirty.capacity() {
+            return FaultResult::NeedsAllocation;
+        }
+
+        let Some(pte) = pte_ext::translate(&self.page_table, vpn).filter(|pte| pte.is_valid())
+        else {
+            if self.frames.state(vpn) == PageState::SwappedOut {
+                // reading the backend may block
+                return FaultResult::NeedsAllocation;
+            }
+            if area.map_type() != MapType::Lazy {
+                return FaultResult::NotMapped;
+            }
+            if let Some(token) = Self::userfault_token(&self.userfault_regions, vpn) {
+                return FaultResult::Userfault {
+                    token,
+                    va,
+                    kind: access,
+                };
+            }
+            if self.pending_frames.len() == self.pending_frames.capacity() || scratch.is_empty() {
+                return FaultResult::NeedsAllocation;
+            }
+            let frame = scratch.take().unwrap();
+            area.map_page(
+                &mut self.page_table,
+                vpn,
+                frame.ppn,
+                area.initial_pte_flags(),
+            );
+            self.pending_frames.push((vpn, frame));
+            if dirty_slot_needed {
+                self.pending_dirty.push(vpn);
+            }
+            return FaultResult::Resolved;
+        };
+        // stale tlb entry, or another hart resolved the fault already
+        if access != AccessType::Write || pte.writable() {
+            return FaultResult::Resolved;
+        }
+
+        let copy = if area.needs_copy(&self.frames, vpn) {
+            match scratch.take() {
+                Some(frame) => Some(frame),
+                None => return FaultResult::NeedsAllocation,
+            }
+        } else {
+            None
+        };
+        if dirty_slot_needed {
+            self.pending_dirty.push(vpn);
+        }
+        if area.write_notify() {
+            if let Some(notifier) = self.write_notifier.as_mut() {
+                notifier(vpn);
+            }
+        }
+        if copy.is_some() {
+            self.cow_breaks += 1;
+        }
+        area.restore_write(&mut self.page_table, &mut self.frames, vpn, copy);
+        FaultResult::Resolved
+    }
+
+    /// Delegate faults on missing pages of lazy areas in `range` to the caller: instead of being
+    /// populated they return `FaultResult::Userfault` carrying `token`, and the page is mapped
+    /// once the fault is resolved by `resolve_userfault`. Write faults on mapped pages are
+    /// still resolved as usual.
+    pub fn register_userfault_region(&mut self, range: VPNRange, token: usize) {
+        self.userfault_regions.push((range, token));
+    }
+
+    /// stop delegating faults of the regions registered with `token`
+    pub fn unregister_userfault_region(&mut self, token: usize) {
+        self.userfault_regions.retain(|(_, t)| *t != token);
+    }
+
+    fn userfault_token(regions: &[(VPNRange, usize)], vpn: VirtPageNum) -> Option<usize> {
+        regions
+            .iter()
+            .find(|(range, _)| range.get_start() <= vpn && vpn < range.get_end())
+            .map(|(_, token)| *token)
+    }
+
+    /// Resolve a fault returned as `FaultResult::Userfault`. Pages mapped meanwhile are left
+    /// untouched. Returns `FaultResult::NotMapped` if `va` is not in a lazy area and
+    /// `FaultResult::OutOfMemory` if frames ran out.
+    pub fn resolve_userfault(
+        &mut self,
+        va: VirtAddr,
+        resolution: UserfaultResolution,
+    ) -> FaultResult {
+        self.settle_faults();
+        let vpn = va.floor();
+        let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.contains(vpn) && area.map_type() == MapType::Lazy)
+        else {
+            return FaultResult::NotMapped;
+        };
+        if pte_ext::translate(&self.page_table, vpn).is_some_and(|pte| pte.is_valid()) {
+            return FaultResult::Resolved;
+        }
+
+        let data = match resolution {
+            UserfaultResolution::Data(data) => data,
+            UserfaultResolution::Zero => &[],
+            UserfaultResolution::Retry => return FaultResult::Resolved,
+        };
+        if !area.fill_page(&mut self.page_table, &mut self.frames, vpn, data) {
+            return FaultResult::OutOfMemory;
+        }
+        self.page_table_usage.record(vpn);
+        if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+            soft_dirty.insert(vpn);
+        }
+        FaultResult::Resolved
+    }
+
+    /// Reserve bookkeeping for `slots` faults resolved by `handle_page_fault_nonalloc`,
+    /// this allocates and must not be invoked from the trap handler.
+    pub fn reserve_fault_slots(&mut self, slots: usize) {
+        self.settle_faults();
+        self.pending_frames.reserve_exact(slots);
+        self.pending_dirty.reserve_exact(slots);
+    }
+
+    /// Hand the pages mapped by `handle_page_fault_nonalloc` to their areas,
+    /// this allocates and must not be invoked from the trap handler.
+    pub fn settle_faults(&mut self) {
+        for (vpn, frame) in self.pending_frames.drain(..) {
+            let still_mapped = pte_ext::translate(&self.page_table, vpn)
+                .is_some_and(|pte| pte.is_valid() && pte.ppn() == frame.ppn);
+            // otherwise the page was unmapped meanwhile, dropping the frame frees it
+            if still_mapped {
+                if let Some(area) = self.areas.iter_mut().find(|area| area.contains(vpn)) {
+                    area.adopt_frame(&mut self.frames, vpn, frame);
+                    self.page_table_usage.record(vpn);
+                }
+            }
+        }
+        if let Some(soft_dirty) = self.soft_dirty.as_mut() {
+            soft_dirty.extend(self.pending_dirty.drain(..));
+        }
+        self.debug_assert_invariants();
+    }
+
+    /// Load the program in `data` with the first of `formats` that recognizes it,
+    /// `None` if none does.
+    pub fn load_program(
+        &mut self,
+        formats: &[&dyn BinFmt],
+        data: &[u8],
+        layout: &ProgramLayout,
+    ) -> Option<LoadResult> {
+        let format = formats.iter().find(|format| format.probe(data))?;
+        Some(format.load(self, data, layout))
+    }
+
+    /// Replace the program of the memory set by the elf in `elf_data`, like exec. Areas flagged
+    /// by `MapArea::with_keep_on_exec` are attached to the new program at the same addresses
+    /// and share their frames, unless they overlap it. The memory set gets a new page table,
+    /// so its token changes, but keeps the asid and the policies, callbacks and pools of the
+    /// memory set; soft-dirty tracking counts all pages of the new program as written and
+    /// the mmap buddy starts empty. Nothing changes if the elf is rejected or frames run out.
+    pub fn replace_from_elf(
+        &mut self,
+        elf_data: &[u8],
+        layout: &ProgramLayout,
+        policy: &ElfLoadPolicy,
+    ) -> Result<(usize, usize), ElfLoadError> {
+        let mut memory_set = MemorySet::new_bare();
+        memory_set.forbid_wx = self.forbid_wx;
+        memory_set.overcommit = self.overcommit;
+        memory_set.commit_limit = self.commit_limit;
+        memory_set.eager_copy_pages = self.eager_copy_pages;
+        memory_set.asid = self.asid;
+        memory_set.swap_backend = self.swap_backend.clone();
+        memory_set
+            .fault_around
+            .set_window(self.fault_around.window());
+        memory_set.mmap_buddy = self
+            .mmap_buddy
+            .as_ref()
+            .and_then(|buddy| VaBuddy::new(buddy.range().get_start().into(), buddy.max_order()));
+        memory_set.soft_dirty = self.soft_dirty.as_ref().map(|_| BTreeSet::new());
+        let entry = Self::load_elf_into(&mut memory_set, elf_data, layout, None, policy)?;
+        for mapping in self.fixed_mappings.iter() {
+            if !memory_set.map_fixed(mapping.name, mapping.vpn, mapping.ppn) {
+                return Err(ElfLoadError::Map(TransactionError::Overlap));
+            }
+        }
+        self.settle_faults();
+        for area in self.areas.iter().filter(|area| area.keeps_on_exec()) {
+            let (start, end) = (area.vpn_range.get_start(), area.vpn_range.get_end());
+            if memory_set.overlaps_any(start, end) || memory_set.fixed_in(start, end) {
+                continue;
+            }
+            let mut kept = MapArea::from_another(area);
+            if area.map_type().is_direct() {
+                memory_set.push(kept, None);
+                continue;
+            }
+            kept.share_frames(
+                &mut memory_set.page_table,
+                &mut memory_set.frames,
+                area,
+                &self.frames,
+                false,
+            );
+            memory_set.push_mapped(kept);
+        }
+        memory_set.canary = self.canary.filter(|canary| {
+            memory_set
+                .areas
+                .iter()
+                .any(|area| area.contains(canary.va().floor()))
+        });
+        memory_set.reclaim_callback = self.reclaim_callback.take();
+        memory_set.write_notifier = self.write_notifier.take();
+        memory_set.zeroed_pool = self.zeroed_pool.take();
+        *self = memory_set;
+        Ok(entry)
+    }
+
+    /// Include sections in elf and trampoline and TrapContext and user stack,
+    /// also returns user_sp and entry point. Fails if the elf is malformed.
+    pub fn from_elf(
+        elf_data: &[u8],
+        trampline_start_va: usize,
+        trampline_start_pa: usize,
+        trap_cx_start_va: usize,
+        user_stack_size: usize,
+    ) -> Result<(MemorySet, usize, usize), ElfLoadError> {
+        Self::load_elf(
+            elf_data,
+            trampline_start_va,
+            trampline_start_pa,
+            trap_cx_start_va,
+            user_stack_size,
+            None,
+            &ElfLoadPolicy::default(),
+        )
+    }
+
+    /// Same as `from_elf`, with segments mapped according to `policy`.
+    pub fn from_elf_with_policy(
+        elf_data: &[u8],
+        trampline_start_va: usize,
+        trampline_start_pa: usize,
+        trap_cx_start_va: usize,
+        user_stack_size: usize,
+        policy: &ElfLoadPolicy,
+    ) -> Result<(MemorySet, usize, usize), ElfLoadError> {
+        Self::load_elf(
+            elf_data,
+            trampline_start_va,
+            trampline_start_pa,
+            trap_cx_start_va,
+            user_stack_size,
+            None,
+            policy,
+        )
+    }
+
+    /// Same as `from_elf`, but read-only segments are backed by frames shared
+    /// through `registry` with all memory sets loaded from the same elf.
+    pub fn from_elf_shared(
+        elf_data: &[u8],
+        trampline_start_va: usize,
+        trampline_start_pa: usize,
+        trap_cx_start_va: usize,
+        user_stack_size: usize,
+        registry: &mut TextSegmentRegistry,
+    ) -> Result<(MemorySet, usize, usize), ElfLoadError> {
+        Self::load_elf(
+            elf_data,
+            trampline_start_va,
+            trampline_start_pa,
+            trap_cx_start_va,
+            user_stack_size,
+            Some(registry),
+            &ElfLoadPolicy::default(),
+        )
+    }
+
+    fn load_elf(
+        elf_data: &[u8],
+        trampline_start_va: usize,
+        trampline_start_pa: usize,
+        trap_cx_start_va: usize,
+        user_stack_size: usize,
+        registry: Option<&mut TextSegmentRegistry>,
+        policy: &ElfLoadPolicy,
+    ) -> Result<(MemorySet, usize, usize), ElfLoadError> {
+        let layout = ProgramLayout {
+            trampoline_va: trampline_start_va,
+            trampoline_pa: trampline_start_pa,
+            trap_cx_start_va,
+            user_stack_size,
+        };
+        let mut memory_set = MemorySet::new_bare();
+        let (user_sp, entry) =
+            Self::load_elf_into(&mut memory_set, elf_data, &layout, registry, policy)?;
+        Ok((memory_set, user_sp, entry))
+    }
+
+    /// map the trampoline, the segments of the elf, the user stack and the trap context
+    /// into `memory_set`, returns user_sp and entry point. The headers are checked before
+    /// anything is mapped, running out of frames fails with `TransactionError::OutOfMemory`.
+    pub(crate) fn load_elf_into(
+        memory_set: &mut MemorySet,
+        elf_data: &[u8],
+        layout: &ProgramLayout,
+        mut registry: Option<&mut TextSegmentRegistry>,
+        policy: &ElfLoadPolicy,
+    ) -> Result<(usize, usize), ElfLoadError> {
+        let plan = elf::plan_with_policy(elf_data, layout, policy)?;
+        if plan
+            .areas
+            .iter()
+            .any(|planned| memory_set.is_wx_forbidden(planned.perm))
+        {
+            return Err(ElfLoadError::Map(TransactionError::WritableExecutable));
+        }
+        let elf_hash = registry
+            .as_ref()
+            .map(|_| TextSegmentRegistry::elf_hash(elf_data));
+        memory_set.map_trampoline(
+            VirtAddr::from(layout.trampoline_va).into(),
+            PhysAddr::from(layout.trampoline_pa).into(),
+        );
+
+        // map program headers of elf, with U flag
+        for planned in plan.areas.iter() {
+            let map_area = MapArea::new(
+                planned.start_va.into(),
+                planned.end_va.into(),
+                planned.map_type,
+                planned.perm,
+            )
+            .with_clone_strategy(planned.clone_strategy)
+            .with_origin(AreaOrigin::Exec);
+            let Some(file_range) = planned.file_range.clone() else {
+                memory_set
+                    .try_push(map_area, None, AllocPolicy::Block)
+                    .map_err(ElfLoadError::Map)?;
+                continue;
+            };
+
+            let data = &elf_data[file_range];
+            match (registry.as_mut(), elf_hash) {
+                (Some(registry), Some(elf_hash))
+                    if planned.clone_strategy == CloneStrategy::Share
+                        && !planned.perm.contains(MapPermission::W) =>
+                {
+                    let key = TextSegmentKey {
+                        elf_hash,
+                        segment: planned.segment,
+                    };
+                    let frames = registry
+                        .get_or_load(key, map_area.page_count(), data)
+                        .ok_or(ElfLoadError::Map(TransactionError::OutOfMemory))?;
+                    memory_set.push_with_frames(map_area, frames);
+                }
+                _ if policy.physical_order_copy() => {
+                    let frames = Self::copy_in_physical_order(map_area.page_count(), data)
+                        .ok_or(ElfLoadError::Map(TransactionError::OutOfMemory))?;
+                    memory_set.push_with_frames(map_area, frames);
+                }
+                _ => memory_set
+                    .try_push(map_area, Some(data), AllocPolicy::Block)
+                    .map_err(ElfLoadError::Map)?,
+            }
+        }
+        if let Some(heap_start) = plan.heap_start {
+            memory_set.create_heap(heap_start.into());
+        }
+        memory_set.program_headers = Some(plan.program_headers);
+
+        // map user stack with U flags
+        let (user_stack_bottom, user_stack_top) = plan.user_stack;
+
+        let rwu = MapPermission::R | MapPermission::W | MapPermission::U;
+        let rw = MapPermission::R | MapPermission::W;
+        let mut user_stack = MapArea::new(
+            user_stack_bottom.into(),
+            user_stack_top.into(),
+            plan.user_stack_type,
+            rwu,
+        )
+        .with_origin(AreaOrigin::Exec)
+        .with_stack();
+        if let Some((max_gap, max_pages)) = plan.user_stack_growth {
+            user_stack = user_stack.with_grows_down(max_gap, max_pages);
+        }
+        memory_set
+            .try_push(user_stack, None, AllocPolicy::Block)
+            .map_err(ElfLoadError::Map)?;
+        memory_set.prepopulate_stack(&plan);
+        for (start_va, end_va, map_perm) in [
+            (user_stack_top, user_stack_top, rwu),
+            (layout.trap_cx_start_va, layout.trampoline_va, rw),
+        ] {
+            // the trap context is not user-accessible but owned by the program
+            memory_set
+                .try_push(
+                    MapArea::new(start_va.into(), end_va.into(), MapType::Framed, map_perm)
+                        .with_origin(AreaOrigin::Exec)
+                        .with_class(AreaClass::User),
+                    None,
+                    AllocPolicy::Block,
+                )
+                .map_err(ElfLoadError::Map)?;
+        }
+
+        Ok((user_stack_top, plan.entry))
+    }
+
+    /// Map what `plan` computed for `elf_data` all at once, the frames of all framed pages
+    /// are reserved first, so that running out of them leaves the memory set untouched,
+    /// the trampoline is unmapped again if it was mapped for the plan. Fails with
+    /// `TransactionError::Overlap` if the memory set has a trampoline elsewhere or its page
+    /// is taken. Returns user_sp and entry point.
+    pub fn apply_plan(
+        &mut self,
+        plan: &LoadPlan,
+        elf_data: &[u8],
+    ) -> Result<(usize, usize), ElfLoadError> {
+        if plan
+            .areas
+            .iter()
+            .any(|area| matches!(&area.file_range, Some(range) if range.end > elf_data.len()))
+        {
+            return Err(ElfLoadError::SegmentOutOfBounds);
+        }
+        let trampoline: VirtPageNum = VirtAddr::from(plan.trampoline_va).into();
+        let trampoline_ppn: PhysPageNum = PhysAddr::from(plan.trampoline_pa).into();
+        let mapped_trampoline = match self.trampoline {
+            None => {
+                let end = VirtPageNum(trampoline.0 + 1);
+                if self.overlaps_any(trampoline, end) || self.fixed_in(trampoline, end) {
+                    return Err(ElfLoadError::Map(TransactionError::Overlap));
+                }
+                self.map_trampoline(trampoline, trampoline_ppn);
+                true
+            }
+            Some(vpn)
+                if vpn == trampoline
+                    && pte_ext::translate(&self.page_table, vpn)
+                        .is_some_and(|pte| pte.ppn() == trampoline_ppn) =>
+            {
+                false
+            }
+            // a second trampoline would leave the first one mapped but untracked
+            Some(_) => return Err(ElfLoadError::Map(TransactionError::Overlap)),
+        };
+        let rwu = MapPermission::R | MapPermission::W | MapPermission::U;
+        let rw = MapPermission::R | MapPermission::W;
+        let (user_stack_bottom, user_stack_top) = plan.user_stack;
+        self.transaction(|txn| {
+            for planned in plan.areas.iter() {
+                let map_area = MapArea::new(
+                    planned.start_va.into(),
+                    planned.end_va.into(),
+                    planned.map_type,
+                    planned.perm,
+                )
+                .with_clone_strategy(planned.clone_strategy)
+                .with_origin(AreaOrigin::Exec);
+                txn.push(
+                    map_area,
+                    planned.file_range.clone().map(|range| &elf_data[range]),
+                )?;
+            }
+            let mut user_stack = MapArea::new(
+                user_stack_bottom.into(),
+                user_stack_top.into(),
+                plan.user_stack_type,
+                rwu,
+            )
+            .with_origin(AreaOrigin::Exec)
+            .with_stack();
+            if let Some((max_gap, max_pages)) = plan.user_stack_growth {
+                user_stack = user_stack.with_grows_down(max_gap, max_pages);
+            }
+            txn.push(user_stack, None)?;
+            for (start_va, end_va, map_perm) in [
+                (user_stack_top, user_stack_top, rwu),
+                (plan.trap_cx.0, plan.trap_cx.1, rw),
+            ] {
+                txn.push(
+                    MapArea::new(start_va.into(), end_va.into(), MapType::Framed, map_perm)
+                        .with_origin(AreaOrigin::Exec)
+                        .with_class(AreaClass::User),
+                    None,
+                )?;
+            }
+            Ok(())
+        })
+        .map_err(|err| {
+            if mapped_trampoline {
+                self.page_table.unmap(trampoline);
+                self.trampoline = None;
+            }
+            ElfLoadError::Map(err)
+        })?;
+        if let Some(heap_start) = plan.heap_start {
+            self.create_heap(heap_start.into());
+        }
+        self.program_headers = Some(plan.program_headers);
+        self.prepopulate_stack(plan);
+        Ok((user_stack_top, plan.entry))
+    }
+
+    /// populate the top pages of a lazy user stack, best effort, pages left out are
+    /// populated on their first access
+    fn prepopulate_stack(&mut self, plan: &LoadPlan) {
+        if plan.user_stack_type != MapType::Lazy {
+            return;
+        }
+        let (user_stack_bottom, user_stack_top) = plan.user_stack;
+        let top = VirtAddr::from(user_stack_top).floor();
+        let bottom = VirtPageNum(top.0.saturating_sub(plan.user_stack_prepopulated))
+            .max(VirtAddr::from(user_stack_bottom).floor());
+        for vpn in VPNRange::new(bottom, top) {
+            self.handle_page_fault(vpn.into(), AccessType::Write);
+        }
+    }
+
+    /// allocate `page_count` frames, then fill them with `data` in ascending physical order,
+    /// `None` if frames run out
+    fn copy_in_physical_order(page_count: usize, data: &[u8]) -> Option<Vec<Arc<FrameTracker>>> {
+        let frames = (0..page_count)
+            .map(|_| frame_alloc().map(Arc::new))
+            .collect::<Option<Vec<_>>>()?;
+        let mut order = (0..page_count).collect::<Vec<_>>();
+        order.sort_by_key(|idx| frames[*idx].ppn);
+        for idx in order {
+            if let Some(src) = data.chunks(PAGE_SIZE).nth(idx) {
+                frames[idx].ppn.get_bytes_array()[..src.len()].copy_from_slice(src);
+            }
+        }
+        Some(frames)
+    }
+}
+
+/// one line per area in address order, like `/proc/self/maps`:
+/// va range, permissions, map type, resident and total pages, origin and tag
+impl fmt::Display for MemorySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut areas = self.areas.iter().collect::<Vec<_>>();
+        areas.sort_by_key(|area| area.vpn_range.get_start());
+        for area in areas {
+            let perm = area.permission();
+            let flag = |bit: MapPermission, c: char| if perm.contains(bit) { c } else { '-' };
+            write!(
+                f,
+                "{:#012x}-{:#012x} {}{}{}{} {:?} {}/{}",
+                area.start_va().0,
+                area.end_va().0,
+                flag(MapPermission::R, 'r'),
+                flag(MapPermission::W, 'w'),
+                flag(MapPermission::X, 'x'),
+                flag(MapPermission::U, 'u'),
+                area.map_type(),
+                area.resident_pages(&self.frames),
+                area.page_count(),
+            )?;
+            if let Some(origin) = area.origin() {
+                write!(f, " {:?}", origin)?;
+            }
+            if let Some(tag) = area.tag() {
+                write!(f, " [{}]", tag)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
@@ -1,18 +1,59 @@
-use page_table::{PhysAddr, VirtAddr};
+use alloc::{sync::Arc, vec::Vec};
+use page_table::{FrameTracker, PhysAddr, PhysPageNum, VirtAddr};
 
-use crate::{MapArea, MapPermission, MapType, MemorySet};
+use crate::{
+    KernelSections, MapArea, MapPermission, MapType, MemorySet, OverlapPolicy, PhysicalOverlap,
+};
 
 pub struct MemorySetBuilder {
     memory_set: MemorySet,
+    overlap_policy: OverlapPolicy,
+    /// physical ranges intentionally mapped both framed and identically
+    aliases: Vec<(PhysPageNum, PhysPageNum)>,
 }
 
 impl MemorySetBuilder {
     pub fn new() -> Self {
         Self {
             memory_set: MemorySet::new_bare(),
+            overlap_policy: OverlapPolicy::default(),
+            aliases: Vec::new(),
         }
     }
 
+    pub fn with_overlap_policy(mut self, overlap_policy: OverlapPolicy) -> Self {
+        self.overlap_policy = overlap_policy;
+        self
+    }
+
+    /// reject writable and executable areas pushed from now on, see `MemorySet::set_forbid_wx`
+    pub fn forbid_wx(mut self) -> Self {
+        self.memory_set.set_forbid_wx(true);
+        self
+    }
+
+    /// declare `start_pa..end_pa` as intentionally mapped by both framed and identical areas
+    pub fn allow_alias(mut self, start_pa: usize, end_pa: usize) -> Self {
+        self.aliases.push((
+            PhysAddr::from(start_pa).floor(),
+            PhysAddr::from(end_pa).ceil(),
+        ));
+        self
+    }
+
+    pub fn push(mut self, map_area: MapArea, data: Option<&[u8]>) -> Self {
+        self.memory_set.push(map_area, data);
+
+        self
+    }
+
+    /// push an area backed by the given frames, see `MemorySet::push_with_frames`
+    pub fn push_with_frames(mut self, map_area: MapArea, frames: Vec<Arc<FrameTracker>>) -> Self {
+        self.memory_set.push_with_frames(map_area, frames);
+
+        self
+    }
+
     pub fn push_identical(
         mut self,
         start_va: usize,
@@ -37,6 +78,16 @@ impl MemorySetBuilder {
         self
     }
 
+    /// push lazily framed memory area, frames are allocated on the first access
+    pub fn push_lazy(mut self, start_va: usize, end_va: usize, map_perm: MapPermission) -> Self {
+        self.memory_set.push(
+            MapArea::new(start_va.into(), end_va.into(), MapType::Lazy, map_perm),
+            None,
+        );
+
+        self
+    }
+
     /// push framed memory area
     pub fn push_framed_with_data(
         mut self,
@@ -53,13 +104,57 @@ impl MemorySetBuilder {
         self
     }
 
+    /// push the sections identically and the device windows at the addresses reported by
+    /// `KernelSections::export_layout`, each area tagged with its name
+    pub fn push_kernel_sections(mut self, sections: &KernelSections) -> Self {
+        let layout = sections.export_layout();
+        let (mapped, windows) = layout.split_at(layout.len() - sections.mmio_addresses().count());
+        for &(name, start, end, perm) in mapped {
+            self.memory_set.push(
+                MapArea::new(start.into(), end.into(), MapType::Identical, perm).with_tag(name),
+                None,
+            );
+        }
+        for (&(name, start, end, perm), pa) in windows.iter().zip(sections.mmio_addresses()) {
+            self.memory_set.push(
+                MapArea::new_mmio(start.into(), end.into(), pa.into(), perm).with_tag(name),
+                None,
+            );
+        }
+
+        self
+    }
+
     pub fn map_trampoline(mut self, va: usize, pa: usize) -> Self {
         self.memory_set
             .map_trampoline(VirtAddr::from(va).into(), PhysAddr::from(pa).into());
         self
     }
 
+    /// Build the memory set, checking it against the overlap policy. The declared aliases
+    /// are kept, see `MemorySet::physical_aliases`.
+    pub fn try_build(mut self) -> Result<MemorySet, PhysicalOverlap> {
+        if self.overlap_policy == OverlapPolicy::Deny {
+            if let Some(overlap) = self
+                .memory_set
+                .physical_overlaps()
+                .into_iter()
+                .find(|overlap| {
+                    !self
+                        .aliases
+                        .iter()
+                        .any(|(start, end)| *start <= overlap.ppn && overlap.ppn < *end)
+                })
+            {
+                return Err(overlap);
+            }
+        }
+        self.memory_set.set_physical_aliases(self.aliases);
+        Ok(self.memory_set)
+    }
+
+    /// build the memory set, panics if it violates the overlap policy
     pub fn build(self) -> MemorySet {
-        self.memory_set
+        self.try_build().unwrap()
     }
 }
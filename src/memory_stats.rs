@@ -0,0 +1,44 @@
+use crate::AreaOrigin;
+
+/// statistics of a memory set, see `MemorySet::stats`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryStats {
+    /// number of map areas
+    pub areas: usize,
+    /// number of virtual pages covered by map areas
+    pub mapped_pages: usize,
+    /// number of frames holding application data
+    pub data_frames: usize,
+    /// pages promised to private writable areas, resident or not
+    pub committed_pages: usize,
+    /// number of frames consumed by page table nodes themselves, root included
+    pub page_table_frames: usize,
+    /// frames of page-colored areas whose color matches their page
+    pub colored_pages: usize,
+    /// frames of page-colored areas that fell back to a frame of another color
+    pub color_misses: usize,
+    /// fault-around window, see `MemorySet::set_fault_around`
+    pub fault_around_pages: usize,
+    /// pages populated ahead of faults
+    pub prefetched_pages: usize,
+    /// prefetched pages accessed afterwards, each of them saved a fault
+    pub faults_avoided: usize,
+    /// prefetched pages unmapped without ever being accessed
+    pub prefetch_wasted: usize,
+    /// frames of areas tagged with an origin, indexed by `AreaOrigin as usize`
+    pub origin_frames: [usize; AreaOrigin::COUNT],
+    /// bytes of pages mapped copy-on-write whose frame is still shared
+    pub cow_shared_bytes: usize,
+    /// bytes of pages backed by a frame of their own
+    pub private_bytes: usize,
+    /// copy-on-write frames copied on a write so far
+    pub cow_breaks: usize,
+}
+
+impl MemoryStats {
+    /// frames of areas tagged with `origin`
+    pub fn frames_of(&self, origin: AreaOrigin) -> usize {
+        self.origin_frames[origin as usize]
+    }
+}
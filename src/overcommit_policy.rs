@@ -0,0 +1,12 @@
+/// whether `MemorySet` promises memory beyond its commit limit, see
+/// `MemorySet::set_commit_limit`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum OvercommitPolicy {
+    /// promise anything, running out of frames is noticed at fault time
+    Always,
+    /// refuse single requests larger than the limit, but let the total exceed it
+    #[default]
+    Heuristic,
+    /// refuse requests that take the committed total beyond the limit
+    Never,
+}
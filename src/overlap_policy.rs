@@ -0,0 +1,10 @@
+/// what `MemorySetBuilder::try_build` does about framed pages whose frame is also mapped
+/// by an identical area
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum OverlapPolicy {
+    /// no check
+    #[default]
+    Allow,
+    /// fail unless the frame lies in an alias declared by `MemorySetBuilder::allow_alias`
+    Deny,
+}
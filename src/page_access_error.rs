@@ -0,0 +1,23 @@
+/// reason `MemorySet::typed_page_mut`, `translated_byte_buffer`, `check_access`, `zero_range`
+/// or a user copy refused to access a page
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PageAccessError {
+    /// the page is not resident
+    NotMapped,
+    /// the page is mapped without write permission
+    NotWritable,
+    /// the page is mapped without read permission
+    NotReadable,
+    /// the page is mapped without execute permission
+    NotExecutable,
+    /// the page belongs to the kernel, not to the user
+    NotUser,
+    /// the address is not aligned for the type
+    Misaligned,
+    /// the value would extend past the end of the page
+    CrossesPage,
+    /// the page is not backed by frames of the memory set, e.g. device or shared memory
+    NotPrivate,
+    /// frames ran out copying a page whose frame is shared
+    OutOfMemory,
+}
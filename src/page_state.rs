@@ -0,0 +1,38 @@
+/// state of a page of an area, stored in the frame table
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PageState {
+    /// no frame backs the page
+    Unallocated,
+    /// backed by a private frame
+    Resident,
+    /// backed by a frame shared copy-on-write, the first write copies it
+    ResidentCow,
+    /// the content lives in the swap backend
+    SwappedOut,
+    /// backed by a frame holding unmodified file content
+    FileClean,
+    /// backed by a frame holding modified file content
+    FileDirty,
+    /// backed by a frame that must stay resident
+    Pinned,
+}
+
+impl PageState {
+    /// whether a page may move from `self` to `next`
+    pub fn can_transition_to(self, next: PageState) -> bool {
+        use PageState::*;
+        match (self, next) {
+            (current, next) if current == next => true,
+            (Pinned, Resident) | (Resident, Pinned) => true,
+            (Pinned, _) => false,
+            (_, Unallocated) => true,
+            (Unallocated, Resident | ResidentCow | FileClean) => true,
+            (Resident, ResidentCow | SwappedOut) => true,
+            (ResidentCow, Resident) => true,
+            (SwappedOut, Resident) => true,
+            (FileClean, FileDirty) | (FileDirty, FileClean) => true,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use alloc::collections::btree_set::BTreeSet;
+use page_table::{PageTable, VPNRange, VirtPageNum};
+
+use crate::pte_ext;
+
+/// bits of vpn resolved by a single level of sv39 page table
+const LEVEL_BITS: usize = 9;
+
+/// tracks the page table nodes created while mapping, the page table never frees
+/// intermediate nodes so once a node is recorded it is counted until the table is dropped
+pub struct PageTableUsage {
+    /// second level nodes, keyed by vpn[2]
+    middle_nodes: BTreeSet<usize>,
+    /// leaf nodes, keyed by vpn[2..1]
+    leaf_nodes: BTreeSet<usize>,
+}
+
+impl PageTableUsage {
+    pub fn new() -> Self {
+        Self {
+            middle_nodes: BTreeSet::new(),
+            leaf_nodes: BTreeSet::new(),
+        }
+    }
+
+    pub fn record(&mut self, vpn: VirtPageNum) {
+        self.middle_nodes.insert(vpn.0 >> (2 * LEVEL_BITS));
+        self.leaf_nodes.insert(vpn.0 >> LEVEL_BITS);
+    }
+
+    pub fn record_range(&mut self, vpn_range: VPNRange) {
+        for vpn in vpn_range {
+            self.record(vpn);
+        }
+    }
+
+    /// `record_range` for pages just mapped, those of megapages need no leaf node
+    pub fn record_mapped(&mut self, page_table: &PageTable, vpn_range: VPNRange) {
+        for vpn in vpn_range {
+            if pte_ext::huge_leaf(page_table, vpn).is_some() {
+                self.middle_nodes.insert(vpn.0 >> (2 * LEVEL_BITS));
+            } else {
+                self.record(vpn);
+            }
+        }
+    }
+
+    /// frames used by page table nodes, including the root
+    pub fn frames(&self) -> usize {
+        1 + self.middle_nodes.len() + self.leaf_nodes.len()
+    }
+}
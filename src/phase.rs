@@ -0,0 +1,8 @@
+/// milestone of an exec at which areas drop permissions, see `MemorySet::advance_phase`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Phase {
+    /// the program is loaded and relocated, e.g. relro becomes read-only
+    LoadComplete,
+    /// the memory set was activated for the first time
+    FirstActivate,
+}
@@ -0,0 +1,8 @@
+use page_table::{PhysPageNum, VirtPageNum};
+
+/// a framed page at `vpn` whose frame `ppn` is also mapped by an identical area
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PhysicalOverlap {
+    pub vpn: VirtPageNum,
+    pub ppn: PhysPageNum,
+}
@@ -0,0 +1,8 @@
+/// where the program headers of a loaded elf are readable in user space,
+/// for the `AT_PHDR`, `AT_PHENT` and `AT_PHNUM` entries of the auxiliary vector
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ProgramHeaders {
+    pub va: usize,
+    pub entry_size: usize,
+    pub count: usize,
+}
@@ -0,0 +1,10 @@
+/// where a loader places the parts of a program that do not come from its image
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgramLayout {
+    pub trampoline_va: usize,
+    pub trampoline_pa: usize,
+    /// start of the trap context, which extends up to the trampoline
+    pub trap_cx_start_va: usize,
+    pub user_stack_size: usize,
+}
@@ -0,0 +1,297 @@
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use page_table::{
+    frame_alloc, FrameTracker, PTEFlags, PageTable, PageTableEntry, PhysPageNum, VirtPageNum,
+};
+use spin::Mutex;
+
+use crate::hw;
+
+/// width of a physical page number in a satp token
+const PPN_WIDTH: usize = 44;
+/// position of the two bits of a pte reserved for supervisor software
+const RSW_SHIFT: usize = 8;
+const RSW_MASK: usize = 0b11 << RSW_SHIFT;
+/// invalid but non-zero pte left behind by `unmap` with the `poison-unmapped` feature
+pub const POISON_PTE: usize = 0xdead_beef << 10;
+/// invalid pte of a page evicted to the swap backend, see `MemorySet::evict`
+pub const SWAPPED_PTE: usize = 1 << RSW_SHIFT;
+/// pages of a sv39 megapage
+pub const MEGAPAGE_PAGES: usize = 512;
+
+/// roots of the page tables whose page flushes are deferred, and whether one was deferred
+static DEFERRED: Mutex<Vec<(usize, bool)>> = Mutex::new(Vec::new());
+/// entries of `DEFERRED`, so that flushes skip the lock unless a batch runs
+static DEFERRED_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// page flushes so far by page table root, roots sharing a slot count for each other
+static FLUSH_GENERATIONS: [AtomicU64; 64] = [const { AtomicU64::new(0) }; 64];
+
+fn root(page_table: &PageTable) -> usize {
+    page_table.token() & ((1usize << PPN_WIDTH) - 1)
+}
+
+fn generation_slot(page_table: &PageTable) -> &'static AtomicU64 {
+    &FLUSH_GENERATIONS[root(page_table) % FLUSH_GENERATIONS.len()]
+}
+
+/// Changes whenever a page of `page_table` loses a mapping or permissions, so that other
+/// harts can tell whether their tlb entries of it may be stale, see `MemorySet::activate`.
+pub fn flush_generation(page_table: &PageTable) -> u64 {
+    generation_slot(page_table).load(Ordering::Acquire)
+}
+
+/// flush the page on the calling hart, unless flushes of the page table are deferred
+fn flush_page(page_table: &PageTable, vpn: VirtPageNum) {
+    generation_slot(page_table).fetch_add(1, Ordering::AcqRel);
+    if DEFERRED_COUNT.load(Ordering::Acquire) > 0 {
+        let root = root(page_table);
+        if let Some(entry) = DEFERRED.lock().iter_mut().find(|(r, _)| *r == root) {
+            entry.1 = true;
+            return;
+        }
+    }
+    hw::flush_page(vpn);
+}
+
+/// defer the page flushes of `unmap` and `remap` on `page_table` until `end_deferred_flushes`
+pub fn defer_flushes(page_table: &PageTable) {
+    DEFERRED.lock().push((root(page_table), false));
+    DEFERRED_COUNT.fetch_add(1, Ordering::Release);
+}
+
+/// stop deferring, returns whether a flush was deferred
+pub fn end_deferred_flushes(page_table: &PageTable) -> bool {
+    let root = root(page_table);
+    let mut deferred = DEFERRED.lock();
+    let Some(idx) = deferred.iter().rposition(|(r, _)| *r == root) else {
+        return false;
+    };
+    DEFERRED_COUNT.fetch_sub(1, Ordering::Release);
+    deferred.swap_remove(idx).1
+}
+
+/// leaf pte of `vpn`, the page table itself only hands out copies. `None` for pages of
+/// megapages, see `huge_leaf`.
+pub fn find_pte(page_table: &PageTable, vpn: VirtPageNum) -> Option<&'static mut PageTableEntry> {
+    let mut ppn = PhysPageNum(root(page_table));
+    let idxs = vpn.indexes();
+    for (level, idx) in idxs.iter().enumerate() {
+        let pte = &mut ppn.get_pte_array()[*idx];
+        if level == idxs.len() - 1 {
+            return Some(pte);
+        }
+        if !pte.is_valid() || is_leaf(pte) {
+            return None;
+        }
+        ppn = pte.ppn();
+    }
+    None
+}
+
+fn is_leaf(pte: &PageTableEntry) -> bool {
+    pte.is_valid()
+        && pte
+            .flags()
+            .intersects(PTEFlags::R | PTEFlags::W | PTEFlags::X)
+}
+
+/// level-1 pte covering `vpn`, the one mapping a megapage, `None` without a level-1 table
+fn megapage_pte(page_table: &PageTable, vpn: VirtPageNum) -> Option<&'static mut PageTableEntry> {
+    let idxs = vpn.indexes();
+    let pte = &PhysPageNum(root(page_table)).get_pte_array()[idxs[0]];
+    if !pte.is_valid() || is_leaf(pte) {
+        return None;
+    }
+    Some(&mut pte.ppn().get_pte_array()[idxs[1]])
+}
+
+/// pte of the megapage covering `vpn`, if it is mapped by one
+pub fn huge_leaf(page_table: &PageTable, vpn: VirtPageNum) -> Option<PageTableEntry> {
+    megapage_pte(page_table, vpn)
+        .filter(|pte| is_leaf(pte))
+        .map(|pte| *pte)
+}
+
+/// like `PageTable::translate`, pages of megapages translate to a pte of their own
+pub fn translate(page_table: &PageTable, vpn: VirtPageNum) -> Option<PageTableEntry> {
+    match huge_leaf(page_table, vpn) {
+        Some(leaf) => Some(PageTableEntry {
+            bits: leaf.bits + ((vpn.0 % MEGAPAGE_PAGES) << 10),
+        }),
+        None => page_table.translate(vpn),
+    }
+}
+
+/// Map the megapage starting at `vpn` to the one starting at `ppn`, both aligned to
+/// `MEGAPAGE_PAGES`, by a leaf of the level-1 table. A missing level-1 table is allocated
+/// and kept in `tables`, the page table only owns the nodes it allocated itself. Returns
+/// false if a page of it is mapped already or no frame is left for the table.
+pub fn map_huge(
+    page_table: &PageTable,
+    tables: &mut Vec<FrameTracker>,
+    vpn: VirtPageNum,
+    ppn: PhysPageNum,
+    flags: PTEFlags,
+    software_bits: u8,
+) -> bool {
+    debug_assert!((vpn.0 | ppn.0) & (MEGAPAGE_PAGES - 1) == 0);
+    let root_pte = &mut PhysPageNum(root(page_table)).get_pte_array()[vpn.indexes()[0]];
+    if is_leaf(root_pte) {
+        return false;
+    }
+    if !root_pte.is_valid() {
+        let Some(table) = frame_alloc() else {
+            return false;
+        };
+        table.ppn.get_pte_array().fill(PageTableEntry::empty());
+        *root_pte = PageTableEntry::new(table.ppn, PTEFlags::V);
+        tables.push(table);
+    }
+    let Some(pte) = megapage_pte(page_table, vpn) else {
+        return false;
+    };
+    if is_leaf(pte)
+        || (pte.is_valid()
+            && pte
+                .ppn()
+                .get_pte_array()
+                .iter()
+                .any(|entry| entry.is_valid()))
+    {
+        return false;
+    }
+    *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    pte.bits |= (software_bits as usize) << RSW_SHIFT;
+    true
+}
+
+/// map the megapage covering `vpn` again with other flags, keeping its software bits
+pub fn remap_huge(page_table: &PageTable, vpn: VirtPageNum, flags: PTEFlags) {
+    if let Some(pte) = megapage_pte(page_table, vpn).filter(|pte| is_leaf(pte)) {
+        *pte = PageTableEntry {
+            bits: PageTableEntry::new(pte.ppn(), flags | PTEFlags::V).bits | (pte.bits & RSW_MASK),
+        };
+        flush_page(page_table, vpn);
+    }
+}
+
+/// unmap the megapage covering `vpn`
+pub fn unmap_huge(page_table: &PageTable, vpn: VirtPageNum) {
+    if let Some(pte) = megapage_pte(page_table, vpn).filter(|pte| is_leaf(pte)) {
+        *pte = PageTableEntry::empty();
+        flush_page(page_table, vpn);
+    }
+}
+
+/// split the megapage covering `vpn`, if any, into pages mapping the same memory,
+/// so that a single page of it can be changed
+fn demote(page_table: &mut PageTable, vpn: VirtPageNum) {
+    let Some(pte) = megapage_pte(page_table, vpn).filter(|pte| is_leaf(pte)) else {
+        return;
+    };
+    let leaf = *pte;
+    *pte = PageTableEntry::empty();
+    let base = vpn.0 - vpn.0 % MEGAPAGE_PAGES;
+    let bits = software_bits(&leaf);
+    for idx in 0..MEGAPAGE_PAGES {
+        let page = VirtPageNum(base + idx);
+        page_table.map(page, PhysPageNum(leaf.ppn().0 + idx), leaf.flags());
+        if bits != 0 {
+            set_software_bits(page_table, page, bits);
+        }
+    }
+    flush_page(page_table, vpn);
+}
+
+pub fn software_bits(pte: &PageTableEntry) -> u8 {
+    ((pte.bits & RSW_MASK) >> RSW_SHIFT) as u8
+}
+
+/// set the rsw bits of a mapped page, returns false if the page is not mapped
+pub fn set_software_bits(page_table: &PageTable, vpn: VirtPageNum, bits: u8) -> bool {
+    assert!(bits <= 0b11, "only two software bits in a pte");
+    match find_pte(page_table, vpn).filter(|pte| pte.is_valid()) {
+        Some(pte) => {
+            pte.bits = (pte.bits & !RSW_MASK) | ((bits as usize) << RSW_SHIFT);
+            true
+        }
+        None => false,
+    }
+}
+
+/// map a mapped page again with other flags, keeping its software bits, and flush it
+pub fn remap(page_table: &mut PageTable, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+    demote(page_table, vpn);
+    let bits = page_table
+        .translate(vpn)
+        .map_or(0, |pte| software_bits(&pte));
+    page_table.unmap(vpn);
+    page_table.map(vpn, ppn, flags);
+    if bits != 0 {
+        set_software_bits(page_table, vpn, bits);
+    }
+    flush_page(page_table, vpn);
+}
+
+/// Clear `flags` out of the accessed and dirty bits of the leaf mapping `vpn` and flush
+/// the page if any was set, returns those that were set. `None` if the page is not mapped.
+/// The bits of a page of a megapage are those of the whole megapage.
+pub fn take_accessed_dirty(
+    page_table: &PageTable,
+    vpn: VirtPageNum,
+    flags: PTEFlags,
+) -> Option<PTEFlags> {
+    let pte = match megapage_pte(page_table, vpn) {
+        Some(pte) if is_leaf(pte) => pte,
+        _ => find_pte(page_table, vpn).filter(|pte| pte.is_valid())?,
+    };
+    let taken = pte.flags() & flags & (PTEFlags::A | PTEFlags::D);
+    if !taken.is_empty() {
+        pte.bits &= !(taken.bits() as usize);
+        flush_page(page_table, vpn);
+    }
+    Some(taken)
+}
+
+/// unmap a page and flush it, the pte is poisoned with the `poison-unmapped` feature
+pub fn unmap(page_table: &mut PageTable, vpn: VirtPageNum) {
+    demote(page_table, vpn);
+    page_table.unmap(vpn);
+    #[cfg(feature = "poison-unmapped")]
+    if let Some(pte) = find_pte(page_table, vpn) {
+        pte.bits = POISON_PTE;
+    }
+    flush_page(page_table, vpn);
+}
+
+pub fn is_poisoned(pte: &PageTableEntry) -> bool {
+    pte.bits == POISON_PTE
+}
+
+/// Leave the swapped marker in the pte of the unmapped page `vpn`, the page table nodes
+/// are allocated if missing.
+pub fn mark_swapped(page_table: &mut PageTable, vpn: VirtPageNum) {
+    if find_pte(page_table, vpn).is_none() {
+        page_table.map(vpn, PhysPageNum(0), PTEFlags::empty());
+    }
+    find_pte(page_table, vpn).unwrap().bits = SWAPPED_PTE;
+}
+
+pub fn is_swapped(pte: &PageTableEntry) -> bool {
+    pte.bits == SWAPPED_PTE
+}
+
+/// clear the swapped marker of `vpn`, returns whether it was set
+pub fn clear_swapped(page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+    match find_pte(page_table, vpn) {
+        Some(pte) if is_swapped(pte) => {
+            pte.bits = if cfg!(feature = "poison-unmapped") {
+                POISON_PTE
+            } else {
+                0
+            };
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,58 @@
+use page_table::VirtPageNum;
+
+use crate::{MemorySet, ReclaimProgress};
+
+/// Incremental reclaim of a memory set, meant to be stepped from an idle or housekeeping
+/// thread so that maintenance stays out of the fault and syscall paths. Each step does a
+/// bounded amount of work and resumes where the previous one stopped.
+pub struct ReclaimDriver {
+    cursor: VirtPageNum,
+    released_total: usize,
+}
+
+impl ReclaimDriver {
+    pub fn new() -> Self {
+        Self {
+            cursor: VirtPageNum(0),
+            released_total: 0,
+        }
+    }
+
+    /// examine at most `budget` resident pages, freeing those that can be populated again on
+    /// demand, see `MemorySet::release_clean_pages`; other harts must flush their tlb afterwards
+    pub fn step(&mut self, memory_set: &mut MemorySet, budget: usize) -> ReclaimProgress {
+        let (released, examined, next) = memory_set.release_clean_pages_from(self.cursor, budget);
+        self.cursor = next.unwrap_or(VirtPageNum(0));
+        self.released_total += released;
+        ReclaimProgress {
+            examined,
+            released,
+            pass_complete: next.is_none(),
+        }
+    }
+
+    /// free up to `n_pages` frames least recently accessed, see `MemorySet::reclaim`;
+    /// other harts must flush their tlb afterwards
+    pub fn reclaim(&mut self, memory_set: &mut MemorySet, n_pages: usize) -> usize {
+        let released = memory_set.reclaim(n_pages);
+        self.released_total += released;
+        released
+    }
+
+    /// zero at most `budget` frames ahead for the zeroed pool of `memory_set`, if any,
+    /// see `MemorySet::set_zeroed_pool`; returns how many were added
+    pub fn scrub(&mut self, memory_set: &mut MemorySet, budget: usize) -> usize {
+        memory_set.refill_zeroed_pool(budget)
+    }
+
+    /// frames freed by all steps so far
+    pub fn released_total(&self) -> usize {
+        self.released_total
+    }
+}
+
+impl Default for ReclaimDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,11 @@
+/// work done by a `ReclaimDriver::step`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReclaimProgress {
+    /// resident pages examined
+    pub examined: usize,
+    /// frames freed
+    pub released: usize,
+    /// the whole address space was walked, the next step starts over
+    pub pass_complete: bool,
+}
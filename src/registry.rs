@@ -0,0 +1,91 @@
+//! memory sets by token, so that a trap handler knowing only satp finds the memory set to
+//! resolve a fault in: `lookup_by_token(satp)`, then `MemorySet::handle_page_fault`
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use page_table::PAGE_SIZE;
+use spin::Mutex;
+
+use crate::{MemorySet, PageState};
+
+pub type SharedMemorySet = Arc<Mutex<MemorySet>>;
+
+/// width of the physical page number of the root page table in satp
+const PPN_WIDTH: usize = 44;
+
+/// a registered memory set and its generation, see `MemorySet::generation`
+type Entry = (Weak<Mutex<MemorySet>>, u64);
+
+/// registered memory sets keyed by the root page table, which ignores mode and asid of satp
+static MEMORY_SETS: Mutex<BTreeMap<usize, Entry>> = Mutex::new(BTreeMap::new());
+
+fn root_ppn(satp: usize) -> usize {
+    satp & ((1usize << PPN_WIDTH) - 1)
+}
+
+/// make the memory set findable by its token until it is dropped or unregistered
+pub fn register(memory_set: &SharedMemorySet) {
+    let (token, generation) = {
+        let memory_set = memory_set.lock();
+        (memory_set.token(), memory_set.generation())
+    };
+    MEMORY_SETS
+        .lock()
+        .insert(root_ppn(token), (Arc::downgrade(memory_set), generation));
+}
+
+pub fn unregister(satp: usize) {
+    MEMORY_SETS.lock().remove(&root_ppn(satp));
+}
+
+/// the memory set activated by `satp`, if it is registered and alive
+pub fn lookup_by_token(satp: usize) -> Option<SharedMemorySet> {
+    let mut memory_sets = MEMORY_SETS.lock();
+    let key = root_ppn(satp);
+    let memory_set = memory_sets.get(&key)?.0.upgrade();
+    if memory_set.is_none() {
+        memory_sets.remove(&key);
+    }
+    memory_set
+}
+
+/// generation of the memory set registered under `satp` if it is alive,
+/// without locking it, see `MemorySet::activate_checked`
+pub fn generation_of(satp: usize) -> Option<u64> {
+    MEMORY_SETS
+        .lock()
+        .get(&root_ppn(satp))
+        .filter(|(memory_set, _)| memory_set.strong_count() > 0)
+        .map(|(_, generation)| *generation)
+}
+
+/// Bytes shared copy-on-write and bytes owned privately by the registered memory sets, like
+/// `MemoryStats::cow_shared_bytes` and `MemoryStats::private_bytes` but counting each frame
+/// once however many memory sets map it. Every registered memory set is locked in turn, so
+/// none of them may be locked by the caller.
+pub fn cow_and_private_bytes() -> (usize, usize) {
+    let memory_sets = MEMORY_SETS
+        .lock()
+        .values()
+        .filter_map(|(memory_set, _)| memory_set.upgrade())
+        .collect::<Vec<_>>();
+    // references to each frame and whether a page maps it copy-on-write
+    let mut frames = BTreeMap::new();
+    for memory_set in memory_sets {
+        for (frame, state) in memory_set.lock().data_frames() {
+            let (_, cow) = frames
+                .entry(frame.ppn.0)
+                .or_insert((Arc::strong_count(frame), false));
+            *cow |= state == PageState::ResidentCow;
+        }
+    }
+    let cow_frames = frames
+        .values()
+        .filter(|(count, cow)| *cow && *count > 1)
+        .count();
+    let private_frames = frames.values().filter(|(count, _)| *count == 1).count();
+    (cow_frames * PAGE_SIZE, private_frames * PAGE_SIZE)
+}
@@ -0,0 +1,51 @@
+use crate::{CloneStrategy, MapType};
+
+/// kind of the pages of a loadable segment, see `ElfLoadPolicy::with_segment_strategy`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SegmentClass {
+    /// executable
+    Text,
+    /// neither writable nor executable
+    Rodata,
+    /// writable, the pages holding the file image
+    Data,
+    /// writable, the whole pages past the file image
+    Bss,
+}
+
+/// how the loader maps the pages of a segment class
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SegmentStrategy {
+    /// map at load, share the frames with clones and, through a `TextSegmentRegistry`,
+    /// with other programs loading the same elf unless the segment is writable
+    Shared,
+    /// map at load, clones copy the pages
+    Eager,
+    /// map at load, clones share the pages copy-on-write
+    CopyOnWrite,
+    /// populate the pages with zeros on first access, only for pages without file content,
+    /// others are mapped `Eager`
+    LazyZero,
+}
+
+impl SegmentStrategy {
+    /// map type and clone strategy of an area of pages holding file content
+    pub(crate) fn file_backed(self) -> (MapType, CloneStrategy) {
+        match self {
+            SegmentStrategy::Shared => (MapType::Framed, CloneStrategy::Share),
+            SegmentStrategy::Eager | SegmentStrategy::LazyZero => {
+                (MapType::Framed, CloneStrategy::EagerCopy)
+            }
+            SegmentStrategy::CopyOnWrite => (MapType::Framed, CloneStrategy::CoW),
+        }
+    }
+}
+
+/// text and rodata shared, data copied, large bss tails populated on demand
+pub fn default_segment_strategy(class: SegmentClass) -> SegmentStrategy {
+    match class {
+        SegmentClass::Text | SegmentClass::Rodata => SegmentStrategy::Shared,
+        SegmentClass::Data => SegmentStrategy::Eager,
+        SegmentClass::Bss => SegmentStrategy::LazyZero,
+    }
+}
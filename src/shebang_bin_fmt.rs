@@ -0,0 +1,47 @@
+use alloc::string::String;
+
+use crate::{BinFmt, LoadResult, MemorySet, ProgramLayout};
+
+/// the interpreter line is cut off here, like linux does
+const MAX_INTERPRETER_LINE: usize = 256;
+
+/// scripts starting with `#!`, nothing is mapped but the interpreter is reported
+#[derive(Default)]
+pub struct ShebangBinFmt;
+
+impl ShebangBinFmt {
+    /// path and optional argument of the interpreter line
+    fn parse(data: &[u8]) -> Option<(&str, Option<&str>)> {
+        let line = data.strip_prefix(b"#!")?;
+        let line = &line[..line.len().min(MAX_INTERPRETER_LINE)];
+        let line = match line.iter().position(|byte| *byte == b'\n') {
+            Some(end) => &line[..end],
+            None => line,
+        };
+        let line = core::str::from_utf8(line).ok()?.trim();
+        let (path, arg) = match line.split_once([' ', '\t']) {
+            Some((path, arg)) => (path, Some(arg.trim()).filter(|arg| !arg.is_empty())),
+            None => (line, None),
+        };
+        (!path.is_empty()).then_some((path, arg))
+    }
+}
+
+impl BinFmt for ShebangBinFmt {
+    fn probe(&self, data: &[u8]) -> bool {
+        Self::parse(data).is_some()
+    }
+
+    fn load(
+        &self,
+        _memory_set: &mut MemorySet,
+        data: &[u8],
+        _layout: &ProgramLayout,
+    ) -> LoadResult {
+        let (path, arg) = Self::parse(data).expect("not a script");
+        LoadResult::NeedsInterpreter {
+            path: String::from(path),
+            arg: arg.map(String::from),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use alloc::sync::Arc;
+use spin::Mutex;
+
+/// storage evicted pages are written to, see `MemorySet::set_swap_backend`
+pub trait SwapBackend: Send {
+    /// store a page, returns the slot it went to, `None` if the backend is full
+    fn write_page(&mut self, page: &[u8]) -> Option<usize>;
+
+    /// read the page stored in `slot` into `page`
+    fn read_page(&mut self, slot: usize, page: &mut [u8]);
+
+    /// the page in `slot` is no longer needed
+    fn free_slot(&mut self, slot: usize);
+}
+
+/// slot of a swap backend holding an evicted page, freed when dropped
+pub(crate) struct SwapSlot {
+    backend: Arc<Mutex<dyn SwapBackend>>,
+    slot: usize,
+}
+
+impl SwapSlot {
+    pub(crate) fn new(backend: Arc<Mutex<dyn SwapBackend>>, slot: usize) -> Self {
+        Self { backend, slot }
+    }
+
+    pub(crate) fn read(&self, page: &mut [u8]) {
+        self.backend.lock().read_page(self.slot, page);
+    }
+}
+
+impl Drop for SwapSlot {
+    fn drop(&mut self) {
+        self.backend.lock().free_slot(self.slot);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::MemorySet;
+
+/// A memory set whose areas are gone but whose trampoline and page table are kept,
+/// see `MemorySet::teardown_in_stages`.
+pub struct Teardown {
+    memory_set: MemorySet,
+}
+
+impl Teardown {
+    pub(crate) fn new(memory_set: MemorySet) -> Self {
+        Self { memory_set }
+    }
+
+    /// token of the remaining page table, it stays valid until `finish`
+    pub fn token(&self) -> usize {
+        self.memory_set.token()
+    }
+
+    /// Phase 2: release the trampoline mapping and the page table, must only be
+    /// invoked once no hart uses the token anymore, i.e. after the final switch.
+    /// Dropping the guard does the same.
+    pub fn finish(self) {}
+}
@@ -0,0 +1,33 @@
+use page_table::{VirtAddr, VirtPageNum};
+
+use crate::MemorySet;
+
+/// A transient area of a memory set, unmapped and freed when the guard is dropped,
+/// see `MemorySet::map_temporary`. The memory set is reachable through the guard meanwhile.
+pub struct TempAreaGuard<'a> {
+    memory_set: &'a mut MemorySet,
+    start_vpn: VirtPageNum,
+}
+
+impl<'a> TempAreaGuard<'a> {
+    pub(crate) fn new(memory_set: &'a mut MemorySet, start_vpn: VirtPageNum) -> Self {
+        Self {
+            memory_set,
+            start_vpn,
+        }
+    }
+
+    pub fn start_va(&self) -> VirtAddr {
+        self.start_vpn.into()
+    }
+
+    pub fn memory_set(&mut self) -> &mut MemorySet {
+        self.memory_set
+    }
+}
+
+impl Drop for TempAreaGuard<'_> {
+    fn drop(&mut self) {
+        self.memory_set.remove_area_with_start_vpn(self.start_vpn);
+    }
+}
@@ -0,0 +1,84 @@
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use page_table::{frame_alloc, FrameTracker, PAGE_SIZE};
+
+/// identifies a read-only segment: hash of the elf image and index of its program header
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct TextSegmentKey {
+    pub elf_hash: u64,
+    pub segment: usize,
+}
+
+/// Frames of read-only segments shared by all memory sets running the same binary,
+/// see `MemorySet::from_elf_shared`. The registry only keeps weak references,
+/// a segment is freed once the last memory set mapping it is gone.
+#[derive(Default)]
+pub struct TextSegmentRegistry {
+    segments: BTreeMap<TextSegmentKey, Vec<Weak<FrameTracker>>>,
+}
+
+impl TextSegmentRegistry {
+    pub fn new() -> Self {
+        Self {
+            segments: BTreeMap::new(),
+        }
+    }
+
+    /// fnv-1a hash of an elf image
+    pub fn elf_hash(elf_data: &[u8]) -> u64 {
+        elf_data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Frames holding the segment, loaded from `data` if the segment is not shared yet.
+    /// `data` is start-aligned but maybe shorter than the `page_count` pages. `None` if
+    /// frames run out.
+    pub fn get_or_load(
+        &mut self,
+        key: TextSegmentKey,
+        page_count: usize,
+        data: &[u8],
+    ) -> Option<Vec<Arc<FrameTracker>>> {
+        if let Some(frames) = self.segments.get(&key) {
+            let frames = frames.iter().filter_map(Weak::upgrade).collect::<Vec<_>>();
+            if frames.len() == page_count {
+                return Some(frames);
+            }
+        }
+
+        let frames = (0..page_count)
+            .map(|page| {
+                let frame = frame_alloc()?;
+                let start = (page * PAGE_SIZE).min(data.len());
+                let src = &data[start..data.len().min(start + PAGE_SIZE)];
+                frame.ppn.get_bytes_array()[..src.len()].copy_from_slice(src);
+                Some(Arc::new(frame))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        self.segments
+            .insert(key, frames.iter().map(Arc::downgrade).collect());
+        Some(frames)
+    }
+
+    /// number of segments still mapped by some memory set
+    pub fn len(&self) -> usize {
+        self.segments
+            .values()
+            .filter(|frames| frames.iter().all(|frame| frame.strong_count() > 0))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// forget the segments no memory set maps anymore
+    pub fn purge(&mut self) {
+        self.segments
+            .retain(|_, frames| frames.iter().all(|frame| frame.strong_count() > 0));
+    }
+}
@@ -0,0 +1,60 @@
+//! thread pointer of the risc-v tls variant I: tp points right past the thread control block,
+//! at the tls block of the executable, and dtv pointers are biased by `DTV_OFFSET`
+
+use xmas_elf::{program::Type, ElfFile};
+
+use crate::elf;
+
+/// bias of dtv pointers, so that signed 12-bit offsets reach a whole 4 KiB of a tls block
+pub const DTV_OFFSET: usize = 0x800;
+
+/// the tls segment of an executable, and the thread control block the runtime keeps below tp
+#[derive(Copy, Clone, Debug)]
+pub struct TlsInfo {
+    /// address of the initialization image
+    pub template_va: usize,
+    /// bytes of the image, the rest of the block up to `mem_size` is zeroed
+    pub file_size: usize,
+    pub mem_size: usize,
+    /// alignment of the block, a power of two
+    pub align: usize,
+    pub tcb_size: usize,
+}
+
+impl TlsInfo {
+    /// `None` if the elf cannot be parsed or has no tls segment
+    pub fn from_elf(elf_data: &[u8], tcb_size: usize) -> Option<Self> {
+        let elf = ElfFile::new(elf_data).ok()?;
+        let ph = (0..elf.header.pt2.ph_count())
+            .filter_map(|i| elf.program_header(i).ok())
+            .find(|ph| ph.get_type() == Ok(Type::Tls))?;
+        Some(Self {
+            template_va: elf::to_usize(ph.virtual_addr()).ok()?,
+            file_size: elf::to_usize(ph.file_size()).ok()?,
+            mem_size: elf::to_usize(ph.mem_size()).ok()?,
+            align: elf::to_usize(ph.align()).ok()?.max(1),
+            tcb_size,
+        })
+    }
+
+    /// bytes to reserve for the tls area of a thread, tcb and worst-case padding included
+    pub fn area_size(&self) -> usize {
+        self.tcb_size + self.align - 1 + self.mem_size
+    }
+}
+
+/// tp of a thread whose tls area starts at `tls_area_va`: past the tcb, aligned up for the
+/// tls block, which is copied from the image to tp
+pub fn compute_tp(tls_area_va: usize, info: &TlsInfo) -> usize {
+    let align = info.align.max(1);
+    debug_assert!(
+        align.is_power_of_two(),
+        "tls alignment is not a power of two"
+    );
+    (tls_area_va + info.tcb_size + align - 1) & !(align - 1)
+}
+
+/// dtv entry of the executable, whose tls block starts at `tp`
+pub fn dtv_entry(tp: usize) -> usize {
+    tp + DTV_OFFSET
+}
@@ -0,0 +1,118 @@
+use alloc::vec::Vec;
+use page_table::{VPNRange, VirtPageNum, PAGE_SIZE};
+
+use crate::{MapArea, MapType, TransactionError};
+
+/// change staged by a `Transaction`
+pub(crate) enum StagedOp<'a> {
+    Push(MapArea, Option<&'a [u8]>),
+    Remove(VirtPageNum),
+    Shrink(VirtPageNum, VirtPageNum),
+    Append(VirtPageNum, VirtPageNum),
+}
+
+/// Changes to a memory set staged by `MemorySet::transaction`. Every change is validated
+/// against a shadow of the areas when it is staged, nothing is mapped or unmapped until
+/// all changes are staged and the frames they need are reserved.
+pub struct Transaction<'a> {
+    /// ranges and types of the areas as they will be after the staged changes
+    shadow: Vec<(VPNRange, MapType)>,
+    ops: Vec<StagedOp<'a>>,
+    /// frames needed by the framed pages staged
+    frames_needed: usize,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(shadow: Vec<(VPNRange, MapType)>) -> Self {
+        Self {
+            shadow,
+            ops: Vec::new(),
+            frames_needed: 0,
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        map_area: MapArea,
+        data: Option<&'a [u8]>,
+    ) -> Result<(), TransactionError> {
+        let range = map_area.vpn_range;
+        if self.overlaps(range.get_start(), range.get_end()) {
+            return Err(TransactionError::Overlap);
+        }
+        if let Some(data) = data {
+            if !map_area.map_type().is_framed() || data.len() > map_area.page_count() * PAGE_SIZE {
+                return Err(TransactionError::InvalidRange);
+            }
+        }
+        if map_area.map_type().is_framed() {
+            self.frames_needed += map_area.page_count();
+        }
+        self.shadow.push((range, map_area.map_type()));
+        self.ops.push(StagedOp::Push(map_area, data));
+        Ok(())
+    }
+
+    /// remove the area starting at `start_vpn`
+    pub fn unmap(&mut self, start_vpn: VirtPageNum) -> Result<(), TransactionError> {
+        let idx = self.find(start_vpn)?;
+        self.shadow.remove(idx);
+        self.ops.push(StagedOp::Remove(start_vpn));
+        Ok(())
+    }
+
+    pub fn shrink_to(
+        &mut self,
+        start_vpn: VirtPageNum,
+        new_end: VirtPageNum,
+    ) -> Result<(), TransactionError> {
+        let idx = self.find(start_vpn)?;
+        let (range, map_type) = self.shadow[idx];
+        if new_end < start_vpn || new_end > range.get_end() {
+            return Err(TransactionError::InvalidRange);
+        }
+        self.shadow[idx] = (VPNRange::new(start_vpn, new_end), map_type);
+        self.ops.push(StagedOp::Shrink(start_vpn, new_end));
+        Ok(())
+    }
+
+    pub fn append_to(
+        &mut self,
+        start_vpn: VirtPageNum,
+        new_end: VirtPageNum,
+    ) -> Result<(), TransactionError> {
+        let idx = self.find(start_vpn)?;
+        let (range, map_type) = self.shadow[idx];
+        if new_end < range.get_end() {
+            return Err(TransactionError::InvalidRange);
+        }
+        if self.overlaps(range.get_end(), new_end) {
+            return Err(TransactionError::Overlap);
+        }
+        if map_type.is_framed() {
+            self.frames_needed += new_end.0 - range.get_end().0;
+        }
+        self.shadow[idx] = (VPNRange::new(start_vpn, new_end), map_type);
+        self.ops.push(StagedOp::Append(start_vpn, new_end));
+        Ok(())
+    }
+
+    fn find(&self, start_vpn: VirtPageNum) -> Result<usize, TransactionError> {
+        self.shadow
+            .iter()
+            .position(|(range, _)| range.get_start() == start_vpn)
+            .ok_or(TransactionError::NotFound)
+    }
+
+    fn overlaps(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        start < end
+            && self
+                .shadow
+                .iter()
+                .any(|(range, _)| range.get_start() < end && start < range.get_end())
+    }
+
+    pub(crate) fn into_ops(self) -> (Vec<StagedOp<'a>>, usize) {
+        (self.ops, self.frames_needed)
+    }
+}
@@ -0,0 +1,19 @@
+/// reason a `MemorySet::transaction` was rolled back
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TransactionError {
+    /// an area would overlap another area
+    Overlap,
+    /// no area starts at the given page
+    NotFound,
+    /// the new end lies outside of what the operation allows, or data does not fit the area
+    InvalidRange,
+    /// not enough frames for all areas staged
+    OutOfMemory,
+    /// frames were needed but the policy did not allow blocking for them, see `MemorySet::try_push`
+    WouldBlock,
+    /// an area would be writable and executable while that is forbidden,
+    /// see `MemorySet::set_forbid_wx`
+    WritableExecutable,
+    /// a kernel area would be user-accessible, see `AreaClass`
+    UserKernelArea,
+}
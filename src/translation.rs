@@ -0,0 +1,11 @@
+use page_table::PageTableEntry;
+
+/// outcome of `MemorySet::translate_checked`
+#[derive(Copy, Clone)]
+pub enum Translation {
+    Mapped(PageTableEntry),
+    /// the page was never mapped
+    NotMapped,
+    /// the page was mapped and unmapped since, only reported with the `poison-unmapped` feature
+    Unmapped,
+}
@@ -0,0 +1,11 @@
+/// how a fault delegated by `FaultResult::Userfault` is resolved,
+/// see `MemorySet::resolve_userfault`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UserfaultResolution<'a> {
+    /// map a page holding the data, the rest of the page is zeroed
+    Data(&'a [u8]),
+    /// map a zeroed page
+    Zero,
+    /// map nothing, the faulting instruction faults again unless the page was mapped meanwhile
+    Retry,
+}
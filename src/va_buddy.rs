@@ -0,0 +1,87 @@
+use alloc::{
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+    vec,
+    vec::Vec,
+};
+use page_table::{VPNRange, VirtAddr, VirtPageNum};
+
+/// Buddy allocator of virtual pages for a window of the mmap region, so that small mmaps
+/// take a block of a power of two pages instead of searching the gaps between all areas,
+/// see `MemorySet::set_mmap_buddy`.
+#[derive(Clone)]
+pub struct VaBuddy {
+    base: VirtPageNum,
+    max_order: usize,
+    /// offsets of the free blocks from `base`, by order
+    free: Vec<BTreeSet<usize>>,
+    /// offsets of the handed out blocks, with their order
+    allocated: BTreeMap<usize, usize>,
+}
+
+impl VaBuddy {
+    /// a window of `1 << max_order` pages at `base`, which must be aligned to the window size
+    pub fn new(base: VirtAddr, max_order: usize) -> Option<Self> {
+        let window = 1usize.checked_shl(max_order as u32)?;
+        if !base.aligned() || base.floor().0 & (window - 1) != 0 {
+            return None;
+        }
+        let mut free = vec![BTreeSet::new(); max_order + 1];
+        free[max_order].insert(0);
+        Some(Self {
+            base: base.floor(),
+            max_order,
+            free,
+            allocated: BTreeMap::new(),
+        })
+    }
+
+    pub fn range(&self) -> VPNRange {
+        VPNRange::new(self.base, VirtPageNum(self.base.0 + (1 << self.max_order)))
+    }
+
+    pub fn max_order(&self) -> usize {
+        self.max_order
+    }
+
+    /// pages in free blocks
+    pub fn free_pages(&self) -> usize {
+        self.free
+            .iter()
+            .enumerate()
+            .map(|(order, blocks)| blocks.len() << order)
+            .sum()
+    }
+
+    /// hand out a block of `1 << order` pages, aligned to its size
+    pub(crate) fn alloc(&mut self, order: usize) -> Option<VirtPageNum> {
+        let mut from = (order..=self.max_order).find(|from| !self.free[*from].is_empty())?;
+        let offset = self.free[from].pop_first().unwrap();
+        while from > order {
+            from -= 1;
+            self.free[from].insert(offset + (1 << from));
+        }
+        self.allocated.insert(offset, order);
+        Some(VirtPageNum(self.base.0 + offset))
+    }
+
+    /// take back the block starting at `vpn`, merging it with its free buddies
+    pub(crate) fn free(&mut self, vpn: VirtPageNum) {
+        let mut offset = vpn.0 - self.base.0;
+        let Some(mut order) = self.allocated.remove(&offset) else {
+            return;
+        };
+        while order < self.max_order && self.free[order].remove(&(offset ^ (1 << order))) {
+            offset &= !(1 << order);
+            order += 1;
+        }
+        self.free[order].insert(offset);
+    }
+
+    /// the handed out blocks
+    pub(crate) fn allocated(&self) -> impl Iterator<Item = VPNRange> + '_ {
+        self.allocated.iter().map(|(offset, order)| {
+            let start = VirtPageNum(self.base.0 + offset);
+            VPNRange::new(start, VirtPageNum(start.0 + (1 << order)))
+        })
+    }
+}
@@ -0,0 +1,15 @@
+use page_table::VirtPageNum;
+
+/// breach of a `VmPolicy` found by `MemorySet::audit`,
+/// areas are identified by their start page
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Violation {
+    /// a user area is writable and executable
+    UserWritableExecutable { start: VirtPageNum },
+    /// an area overlapping the stack is executable
+    ExecutableStack { start: VirtPageNum },
+    /// the trampoline is mapped user-accessible
+    UserTrampoline { vpn: VirtPageNum },
+    /// an identical area overlaps the user range
+    IdenticalInUserRange { start: VirtPageNum },
+}
@@ -0,0 +1,58 @@
+use page_table::VPNRange;
+
+/// invariants checked by `MemorySet::audit`
+#[derive(Copy, Clone)]
+pub struct VmPolicy {
+    /// no user area is both writable and executable
+    forbid_user_wx: bool,
+    /// the trampoline is not user-accessible
+    kernel_only_trampoline: bool,
+    /// areas overlapping the stack are not executable
+    stack: Option<VPNRange>,
+    /// identical areas do not overlap the user range
+    user_range: Option<VPNRange>,
+}
+
+impl VmPolicy {
+    /// forbid user areas that are writable and executable and a user-accessible trampoline
+    pub fn new() -> Self {
+        Self {
+            forbid_user_wx: true,
+            kernel_only_trampoline: true,
+            stack: None,
+            user_range: None,
+        }
+    }
+
+    pub fn with_stack(mut self, stack: VPNRange) -> Self {
+        self.stack = Some(stack);
+        self
+    }
+
+    pub fn with_user_range(mut self, user_range: VPNRange) -> Self {
+        self.user_range = Some(user_range);
+        self
+    }
+
+    pub fn forbid_user_wx(&self) -> bool {
+        self.forbid_user_wx
+    }
+
+    pub fn kernel_only_trampoline(&self) -> bool {
+        self.kernel_only_trampoline
+    }
+
+    pub fn stack(&self) -> Option<VPNRange> {
+        self.stack
+    }
+
+    pub fn user_range(&self) -> Option<VPNRange> {
+        self.user_range
+    }
+}
+
+impl Default for VmPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,12 @@
+use page_table::VPNRange;
+
+/// part of the user address space a free region is searched in
+#[derive(Copy, Clone)]
+pub enum Window {
+    /// below 4 GiB, like `MAP_32BIT`
+    Low32,
+    /// above all user areas, one guard page apart
+    Default,
+    /// within the given pages
+    Custom(VPNRange),
+}
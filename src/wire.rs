@@ -0,0 +1,134 @@
+/// Version of the numeric codes produced by the `to_wire` methods of `MapPermission`,
+/// `MapType`, `CloneStrategy` and `AccessHint`. Codes are never reused, new codes bump it.
+pub const WIRE_VERSION: u16 = 2;
+
+#[cfg(test)]
+mod tests {
+    use crate::{AccessHint, CloneStrategy, MapPermission, MapType};
+
+    const MAP_TYPES: [MapType; 5] = [
+        MapType::Identical,
+        MapType::Framed,
+        MapType::Lazy,
+        MapType::Shared,
+        MapType::Mmio,
+    ];
+
+    const CLONE_STRATEGIES: [CloneStrategy; 5] = [
+        CloneStrategy::EagerCopy,
+        CloneStrategy::CoW,
+        CloneStrategy::Share,
+        CloneStrategy::Zero,
+        CloneStrategy::Skip,
+    ];
+
+    const ACCESS_HINTS: [AccessHint; 2] = [AccessHint::Random, AccessHint::Sequential];
+
+    /// fails to compile when a variant is added without extending the lists above
+    #[allow(dead_code)]
+    fn listed(map_type: MapType, clone_strategy: CloneStrategy, access_hint: AccessHint) {
+        match map_type {
+            MapType::Identical
+            | MapType::Framed
+            | MapType::Lazy
+            | MapType::Shared
+            | MapType::Mmio => {}
+        }
+        match clone_strategy {
+            CloneStrategy::EagerCopy
+            | CloneStrategy::CoW
+            | CloneStrategy::Share
+            | CloneStrategy::Zero
+            | CloneStrategy::Skip => {}
+        }
+        match access_hint {
+            AccessHint::Random | AccessHint::Sequential => {}
+        }
+    }
+
+    #[test]
+    fn map_permission_round_trips() {
+        for bits in 0..=u8::MAX {
+            let Some(perm) = MapPermission::from_bits(bits) else {
+                continue;
+            };
+            assert_eq!(MapPermission::from_wire(perm.to_wire()), Some(perm));
+        }
+    }
+
+    #[test]
+    fn map_permission_codes_are_stable() {
+        assert_eq!(MapPermission::empty().to_wire(), 0);
+        assert_eq!(MapPermission::R.to_wire(), 1 << 0);
+        assert_eq!(MapPermission::W.to_wire(), 1 << 1);
+        assert_eq!(MapPermission::X.to_wire(), 1 << 2);
+        assert_eq!(MapPermission::U.to_wire(), 1 << 3);
+    }
+
+    #[test]
+    fn map_permission_rejects_unknown_bits() {
+        for code in 0..=u8::MAX {
+            match MapPermission::from_wire(code) {
+                Some(perm) => assert_eq!(perm.to_wire(), code),
+                None => assert_ne!(code & !0b1111, 0),
+            }
+        }
+    }
+
+    #[test]
+    fn map_type_round_trips() {
+        for map_type in MAP_TYPES {
+            assert_eq!(MapType::from_wire(map_type.to_wire()), Some(map_type));
+        }
+        let codes = MAP_TYPES.map(MapType::to_wire);
+        assert_eq!(codes, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn map_type_rejects_unknown_codes() {
+        for code in 0..=u8::MAX {
+            match MapType::from_wire(code) {
+                Some(map_type) => assert_eq!(map_type.to_wire(), code),
+                None => assert!(!MAP_TYPES.iter().any(|t| t.to_wire() == code)),
+            }
+        }
+    }
+
+    #[test]
+    fn clone_strategy_round_trips() {
+        for strategy in CLONE_STRATEGIES {
+            assert_eq!(CloneStrategy::from_wire(strategy.to_wire()), Some(strategy));
+        }
+        let codes = CLONE_STRATEGIES.map(CloneStrategy::to_wire);
+        assert_eq!(codes, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn clone_strategy_rejects_unknown_codes() {
+        for code in 0..=u8::MAX {
+            match CloneStrategy::from_wire(code) {
+                Some(strategy) => assert_eq!(strategy.to_wire(), code),
+                None => assert!(!CLONE_STRATEGIES.iter().any(|s| s.to_wire() == code)),
+            }
+        }
+    }
+
+    #[test]
+    fn access_hint_round_trips() {
+        for hint in ACCESS_HINTS {
+            assert_eq!(AccessHint::from_wire(hint.to_wire()), Some(hint));
+        }
+        let codes = ACCESS_HINTS.map(AccessHint::to_wire);
+        assert_eq!(codes, [0, 1]);
+    }
+
+    #[test]
+    fn access_hint_rejects_unknown_codes() {
+        for code in 0..=u8::MAX {
+            match AccessHint::from_wire(code) {
+                Some(hint) => assert_eq!(hint.to_wire(), code),
+                None => assert!(!ACCESS_HINTS.iter().any(|h| h.to_wire() == code)),
+            }
+        }
+    }
+}
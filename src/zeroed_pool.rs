@@ -0,0 +1,83 @@
+use alloc::vec::Vec;
+use page_table::{frame_alloc, FrameTracker};
+
+/// Frames zeroed ahead of time, so that populating a lazy page skips zeroing a fresh frame.
+/// Filled from an idle or housekeeping thread, see `ReclaimDriver::scrub`, and drawn from
+/// first by `MemorySet::handle_page_fault`. Hits and misses tell how to size it.
+pub struct ZeroedPool {
+    frames: Vec<FrameTracker>,
+    capacity: usize,
+    hits: usize,
+    misses: usize,
+}
+
+impl ZeroedPool {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: Vec::with_capacity(capacity),
+            capacity,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// add at most `budget` frames, zeroed by `frame_alloc`, returns how many were added
+    pub fn fill(&mut self, budget: usize) -> usize {
+        let mut added = 0;
+        while added < budget && self.frames.len() < self.capacity {
+            match frame_alloc() {
+                Some(frame) => self.frames.push(frame),
+                None => break,
+            }
+            added += 1;
+        }
+        added
+    }
+
+    /// give all frames back, e.g. when memory runs low
+    pub fn drain(&mut self) -> usize {
+        let drained = self.frames.len();
+        self.frames.clear();
+        drained
+    }
+
+    pub(crate) fn take(&mut self) -> Option<FrameTracker> {
+        let frame = self.frames.pop();
+        if frame.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        frame
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// frames taken from the pool
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// frames needed while the pool was empty
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// hits in percent of all frames needed, 0 before the first
+    pub fn hit_rate(&self) -> usize {
+        match self.hits + self.misses {
+            0 => 0,
+            total => self.hits * 100 / total,
+        }
+    }
+}